@@ -1,4 +1,5 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
 
 /**
  Parse CLI arguments for the olcs CLI.
@@ -8,8 +9,8 @@ use clap::Parser;
 #[command(about = "Run common team commands easily")]
 pub struct Cli {
 
-    /** Override parameter (key=value) */
-    #[arg(short, long = "param")]
+    /** Override parameter (key=value); repeat for multi-value parameters */
+    #[arg(short, long = "param", global = true)]
     pub param: Vec<String>,
 
     /** Show config and exit */
@@ -25,13 +26,105 @@ pub struct Cli {
     pub switch_param_group: bool,
 
     /** Run in interactive mode */
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with = "no_input")]
     pub interactive: bool,
 
+    /** Never prompt: fail and list any missing required parameters (for CI) */
+    #[arg(long, global = true)]
+    pub no_input: bool,
+
+    /** Prompt only for parameters not supplied with --param */
+    #[arg(long, conflicts_with = "no_input", global = true)]
+    pub prompt_missing: bool,
+
     /** Run a specific command by name */
     pub command: Option<String>,
 
     /** Show all available commands from commands.yaml */
     #[arg(short, long)]
     pub list_cmds: bool,
+
+    /** Render and show the command without executing it */
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /** Print only the raw rendered command (implies --dry-run), e.g. to pipe into `pbcopy` or `eval` */
+    #[arg(long, global = true)]
+    pub print: bool,
+
+    /** Run commands marked `confirm` and commands in protected groups without asking */
+    #[arg(short, long, global = true)]
+    pub yes: bool,
+
+    /** Run the command once in each of these param groups, e.g. `--groups dev,qa,uat` */
+    #[arg(long, value_delimiter = ',', requires = "command")]
+    pub groups: Vec<String>,
+
+    /** Run the command once in every param group */
+    #[arg(long, conflicts_with = "groups", requires = "command")]
+    pub all_groups: bool,
+
+    /** With --groups, --all-groups or `run`, run everything at the same time, with labelled output */
+    #[arg(long, global = true)]
+    pub parallel: bool,
+
+    /** Read the config files from this directory only, instead of searching for them */
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config_dir: Option<PathBuf>,
+
+    /** Use this commands file instead of searching for scli.commands.yaml */
+    #[arg(long, global = true, value_name = "PATH")]
+    pub commands_file: Option<PathBuf>,
+
+    /** Use this params file instead of searching for scli.params.yaml */
+    #[arg(long, global = true, value_name = "PATH")]
+    pub params_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub subcommand: Option<SubCommand>,
+}
+
+/** Built-in subcommands */
+#[derive(Subcommand, Debug, Clone)]
+pub enum SubCommand {
+    /** Run several commands, one after the other or with --parallel at the same time */
+    Run {
+        #[arg(required = true)]
+        commands: Vec<String>,
+    },
+    /** Inspect the loaded configuration */
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /** Manage secrets used by the `secret` template filter */
+    Secret {
+        #[command(subcommand)]
+        action: SecretAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigAction {
+    /** Show the files read, lowest precedence first, and the file each command and param came from */
+    Sources,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SecretAction {
+    /** Store a secret (prompts for the value if not given) */
+    Set { key: String, value: Option<String> },
+    /** Print a secret */
+    Get { key: String },
+    /** List the names of stored secrets */
+    List,
+    /** Remove a secret */
+    Rm { key: String },
+}
+
+/** Names of the built-in subcommands (and `help`), which commands outside a category cannot use. */
+pub fn subcommand_names() -> Vec<String> {
+    let mut names: Vec<String> = Cli::command().get_subcommands().map(|c| c.get_name().to_string()).collect();
+    names.push("help".to_string());
+    names
 }
@@ -1,29 +1,116 @@
+use crate::commands::choices::{has_choices, resolve_choices};
+use crate::commands::errors::{CommandError, MissingParam};
+use crate::commands::runner::command_exec_options;
+use crate::commands::validation::{parse_bool, parse_typed};
 use crate::config::models::CommandDef;
-use crate::config::ParamDef;
-use dialoguer::Input;
+use crate::config::{Config, GlobalContext, ParamDef, ParamType};
+use dialoguer::{Confirm, Input, MultiSelect, Password, Select};
 use regex::Regex;
+use serde_json::Value;
 use std::collections::HashMap;
 
+/**
+ Build parameter overrides from `key=value` CLI arguments. Repeated keys
+ accumulate their values in order. An argument without `key=` is a usage error.
+*/
+pub fn parse_param_overrides(raw: &[String]) -> Result<HashMap<String, Vec<String>>, CommandError> {
+    let mut overrides: HashMap<String, Vec<String>> = HashMap::new();
+    for param in raw {
+        match param.split_once('=') {
+            Some((k, v)) if !k.trim().is_empty() => overrides.entry(k.to_string()).or_default().push(v.to_string()),
+            _ => {
+                return Err(CommandError::Usage(format!(
+                    "Invalid parameter '{}': expected -p name=value",
+                    param
+                )));
+            }
+        }
+    }
+    Ok(overrides)
+}
+
+/**
+ The config and context params are collected in. `choices_from` commands run with the
+ env, `shell` and `cwd` the command would get in the context's active group.
+*/
+pub struct ParamSource<'a> {
+    pub cfg: &'a Config,
+    pub ctx: &'a GlobalContext,
+    /** Leave params with `choices_from` out (to be collected for each group separately) */
+    pub defer_group_choices: bool,
+    /** Prefix prompts with the active group (collecting for several groups) */
+    pub show_group: bool,
+}
+
+impl<'a> ParamSource<'a> {
+    pub fn new(cfg: &'a Config, ctx: &'a GlobalContext) -> Self {
+        Self { cfg, ctx, defer_group_choices: false, show_group: false }
+    }
+}
+
 /**
  Collect arguments for a command, prompting the user for any required
  arguments that are not provided via overrides. Defaults are honored,
  and optional arguments are skipped if not provided.
+
+ Overrides for parameters with a `choices` list are validated and an
+ error is returned if a value is not one of the allowed choices.
+ Parameters marked `multi` are collected as arrays (the last override wins
+ for repeated keys of other parameters). Values are checked against the
+ parameter's `type` and converted to native template values; invalid
+ prompted input is re-prompted. Required parameters left without a value
+ are reported together as `MissingParams`.
 */
 pub fn substitute_parameters(
     cmd: &CommandDef,
-    overrides: Option<&HashMap<String, String>>,
+    overrides: Option<&HashMap<String, Vec<String>>>,
     prompt_for_missing: bool,
-) -> HashMap<String, String> {
-    let mut collected = overrides.cloned().unwrap_or_default();
+    source: &ParamSource,
+) -> Result<HashMap<String, Value>, CommandError> {
+    let mut collected: HashMap<String, Value> = HashMap::new();
 
-    // find placeholders
-    let re = Regex::new(r"\{\{(.*?)}}").unwrap();
+    // convert and validate provided values
+    for (name, values) in overrides.into_iter().flatten() {
+        let value = match cmd.params.iter().find(|p| &p.name == name) {
+            Some(param) => override_value(param, values).map_err(CommandError::InvalidParam)?,
+            None => Value::from(values.last().cloned().unwrap_or_default()),
+        };
+        collected.insert(name.clone(), value);
+    }
 
-    for capture in re.captures_iter(&cmd.exec) {
-        let placeholder = capture[1].trim();
+    collect_missing(cmd, &mut collected, prompt_for_missing, source)?;
+    Ok(collected)
+}
 
+/**
+ Collect arguments for a command run on behalf of another (e.g. a pre-command),
+ starting from the parent's values. Only parameters the parent does not have are
+ prompted for or taken from their defaults.
+*/
+pub fn inherit_parameters(
+    cmd: &CommandDef,
+    parent_args: &HashMap<String, Value>,
+    prompt_for_missing: bool,
+    source: &ParamSource,
+) -> Result<HashMap<String, Value>, CommandError> {
+    let mut collected = parent_args.clone();
+    collect_missing(cmd, &mut collected, prompt_for_missing, source)?;
+    Ok(collected)
+}
+
+/** Add a value for every parameter of the command not yet in `collected`. Required
+    parameters left without a value are reported together as `MissingParams`. */
+fn collect_missing(
+    cmd: &CommandDef,
+    collected: &mut HashMap<String, Value>,
+    prompt_for_missing: bool,
+    source: &ParamSource,
+) -> Result<(), CommandError> {
+    let mut missing: Vec<MissingParam> = Vec::new();
+
+    for name in parameter_names(cmd) {
         // skip already provided
-        if collected.contains_key(placeholder) {
+        if collected.contains_key(&name) {
             continue;
         }
 
@@ -31,51 +118,427 @@ pub fn substitute_parameters(
         let param = cmd
             .params
             .iter()
-            .find(|p| p.name == placeholder);
+            .find(|p| p.name == name);
 
         // fallback ParamDef when missing
         let fallback = ParamDef {
-            name: placeholder.to_string(),
-            prompt: "".to_string(),
-            optional: false,
-            default: None,
+            name: name.clone(),
+            ..Default::default()
         };
 
         let param = param.unwrap_or(&fallback);
+        if source.defer_group_choices && param.choices_from.is_some() {
+            continue;
+        }
 
-        // build prompt
-        let mut prompt = if param.prompt.trim().is_empty() {
-            format!("Enter value for '{}'", param.name)
-        } else {
-            param.prompt.clone()
-        };
+        match collect_value(cmd, param, collected, prompt_for_missing, source)? {
+            Some(value) => {
+                collected.insert(param.name.clone(), value);
+            }
+            None => missing.push(MissingParam {
+                name: param.name.clone(),
+                prompt: param.prompt.clone(),
+            }),
+        }
+    }
 
-        let mut input = Input::new();
-        let mut value = String::new();
+    if !missing.is_empty() {
+        return Err(CommandError::MissingParams(missing));
+    }
+    Ok(())
+}
 
-        // default value
-        if let Some(default) = &param.default {
-            input = input.default(default.clone()).show_default(false);
-            prompt = format!("{prompt} [default: {default}]");
-            value = default.clone();
+/**
+ Names of the parameters a command needs: variables referenced in its templates
+ (in order of appearance) and step conditions, followed by any other declared params. Loop and `set`
+ variables, captured output, function calls and `params.*` lookups are not parameters.
+*/
+fn parameter_names(cmd: &CommandDef) -> Vec<String> {
+    let var_re = Regex::new(r"\{\{-?\s*([A-Za-z_][A-Za-z0-9_]*)\s*(\()?").unwrap();
+    let bound_re = Regex::new(
+        r"\{%-?\s*(?:for\s+([A-Za-z_][A-Za-z0-9_]*)(?:\s*,\s*([A-Za-z_][A-Za-z0-9_]*))?\s+in|set(?:_global)?\s+([A-Za-z_][A-Za-z0-9_]*))",
+    )
+    .unwrap();
+
+    let templates = cmd.templates();
+
+    let mut bound: Vec<&str> = templates
+        .iter()
+        .flat_map(|t| bound_re.captures_iter(t))
+        .flat_map(|c| [c.get(1), c.get(2), c.get(3)])
+        .flatten()
+        .map(|m| m.as_str())
+        .collect();
+    bound.extend(cmd.captured_names());
+
+    let referenced = templates
+        .iter()
+        .flat_map(|t| var_re.captures_iter(t))
+        .filter(|c| c.get(2).is_none())
+        .map(|c| c.get(1).unwrap().as_str())
+        .chain(cmd.conditions().into_iter().flat_map(condition_variables));
+
+    let mut names: Vec<String> = Vec::new();
+    for name in referenced {
+        if name == "params" || name == "loop" || bound.contains(&name) {
+            continue;
+        }
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
         }
-        // optional field
-        else if param.optional {
-            input = input.allow_empty(true);
-            prompt = format!("{prompt} [optional]");
+    }
+
+    for param in &cmd.params {
+        if !names.contains(&param.name) {
+            names.push(param.name.clone());
         }
+    }
+    names
+}
 
-        // interactive input
-        if prompt_for_missing {
-            value = input
-                .with_prompt(prompt)
+/**
+ Variables read by a condition (a Tera expression such as `env == "prod" and not dry`):
+ identifiers outside string literals, except keywords, attributes (`.name`), filters
+ (`| name`), tests (`is defined`), function calls and their keyword arguments.
+*/
+fn condition_variables(condition: &str) -> Vec<&str> {
+    const KEYWORDS: [&str; 12] = ["and", "or", "not", "in", "is", "if", "else", "true", "false", "True", "False", "none"];
+    let token_re = Regex::new(
+        r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'|`[^`]*`|([.|]\s*)?\b([A-Za-z_][A-Za-z0-9_]*)\b"#,
+    )
+    .unwrap();
+
+    let mut names = Vec::new();
+    let mut previous: Vec<&str> = Vec::new();
+    for capture in token_re.captures_iter(condition) {
+        let Some(word) = capture.get(2) else {
+            continue;
+        };
+        let name = word.as_str();
+        // function calls and keyword arguments (`default(value=x)`)
+        let rest = condition[word.end()..].trim_start();
+        let is_call_or_arg = rest.starts_with('(') || (rest.starts_with('=') && !rest.starts_with("=="));
+        let after_is = matches!(previous.as_slice(), [.., "is"] | [.., "is", "not"]);
+        let skip = capture.get(1).is_some() || is_call_or_arg || after_is || KEYWORDS.contains(&name);
+        previous.push(name);
+        if !skip {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/** Convert the CLI overrides for a declared parameter into its value. */
+fn override_value(param: &ParamDef, values: &[String]) -> Result<Value, String> {
+    if param.multi {
+        return split_multi(values)
+            .iter()
+            .map(|item| {
+                validate_choice(param, item)?;
+                parse_typed(param, item)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array);
+    }
+
+    let value = values.last().cloned().unwrap_or_default();
+    validate_choice(param, &value)?;
+    parse_typed(param, &value)
+}
+
+/** Convert each value of a multi-value parameter to its typed value. */
+fn parse_typed_list(param: &ParamDef, values: &[String]) -> Result<Value, String> {
+    values
+        .iter()
+        .map(|v| parse_typed(param, v))
+        .collect::<Result<Vec<_>, _>>()
+        .map(Value::Array)
+}
+
+/** Split repeated and/or comma-separated values into a flat list. */
+fn split_multi(values: &[String]) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/**
+ Get the value for a parameter that was not provided: prompt when allowed,
+ otherwise fall back to the default. Returns None for missing required values.
+*/
+fn collect_value(
+    cmd: &CommandDef,
+    param: &ParamDef,
+    collected: &HashMap<String, Value>,
+    prompt_for_missing: bool,
+    source: &ParamSource,
+) -> Result<Option<Value>, CommandError> {
+    // build prompt
+    let mut prompt = if param.prompt.trim().is_empty() {
+        format!("Enter value for '{}'", param.name)
+    } else {
+        param.prompt.clone()
+    };
+    if source.show_group
+        && let Some(group) = &source.ctx.current_group
+    {
+        prompt = format!("[{group}] {prompt}");
+    }
+    let choices = || -> Result<Vec<String>, CommandError> {
+        let exec_opts = command_exec_options(cmd, source.cfg, source.ctx, collected)?;
+        resolve_choices(param, &exec_opts).map_err(CommandError::Execute)
+    };
+
+    // multiple values
+    if param.multi {
+        let defaults = split_multi(param.default.as_slice());
+        let values = if !prompt_for_missing {
+            defaults
+        } else if has_choices(param) {
+            let choices = choices()?;
+            prompt_multi_choice(param, &choices, &defaults, &prompt).map_err(CommandError::InvalidParam)?
+        } else {
+            let mut input = Input::<String>::new()
+                .allow_empty(param.optional || !defaults.is_empty())
+                .validate_with(|text: &String| -> Result<(), String> {
+                    parse_typed_list(param, &split_multi(std::slice::from_ref(text))).map(|_| ())
+                });
+            if let Some(default) = &param.default {
+                input = input.default(default.clone()).show_default(false);
+                prompt = format!("{prompt} [default: {default}]");
+            }
+            let text = input
+                .with_prompt(format!("{prompt} (comma-separated)"))
                 .interact_text()
                 .unwrap_or_default();
+            split_multi(&[text])
+        };
+
+        if values.is_empty() && !param.optional {
+            return Ok(None);
+        }
+        return parse_typed_list(param, &values).map(Some).map_err(CommandError::InvalidParam);
+    }
+
+    // selection from a list of choices
+    if has_choices(param) {
+        let mut value = param.default.clone().unwrap_or_default();
+        if prompt_for_missing {
+            let choices = choices()?;
+            value = prompt_choice(param, &choices, &prompt).map_err(CommandError::InvalidParam)?;
+        }
+        if !value.is_empty() || param.optional {
+            return parse_typed(param, &value).map(Some).map_err(CommandError::InvalidParam);
+        }
+        return Ok(None);
+    }
+
+    // yes/no confirmation
+    if param.kind == ParamType::Bool && prompt_for_missing {
+        let default = param.default.as_deref().and_then(parse_bool).unwrap_or(false);
+        let value = Confirm::new()
+            .with_prompt(prompt)
+            .default(default)
+            .interact()
+            .map_err(|e| CommandError::InvalidParam(format!("Prompt for '{}' failed: {}", param.name, e)))?;
+        return Ok(Some(Value::from(value)));
+    }
+
+    // masked input; the default is never shown
+    if param.secret && prompt_for_missing {
+        let has_default = param.default.is_some();
+        let mut value = Password::new()
+            .with_prompt(if has_default { format!("{prompt} [default: ****]") } else { prompt })
+            .allow_empty_password(param.optional || has_default)
+            .validate_with(|text: &String| -> Result<(), String> {
+                if text.is_empty() {
+                    return Ok(());
+                }
+                parse_typed(param, text).map(|_| ())
+            })
+            .interact()
+            .map_err(|e| CommandError::InvalidParam(format!("Prompt for '{}' failed: {}", param.name, e)))?;
+        if value.is_empty() {
+            value = param.default.clone().unwrap_or_default();
         }
         if !value.is_empty() || param.optional {
-            collected.insert(param.name.clone(), value);
+            return parse_typed(param, &value).map(Some).map_err(CommandError::InvalidParam);
         }
+        return Ok(None);
+    }
+
+    let mut input = Input::new().validate_with(|text: &String| -> Result<(), String> {
+        parse_typed(param, text).map(|_| ())
+    });
+    let mut value = String::new();
+
+    // default value
+    if let Some(default) = &param.default {
+        input = input.default(default.clone()).show_default(false);
+        prompt = format!("{prompt} [default: {default}]");
+        value = default.clone();
+    }
+    // optional field
+    else if param.optional {
+        input = input.allow_empty(true);
+        prompt = format!("{prompt} [optional]");
+    }
+
+    // interactive input
+    if prompt_for_missing {
+        value = input
+            .with_prompt(prompt)
+            .interact_text()
+            .unwrap_or_default();
+    }
+    if !value.is_empty() || param.optional {
+        return parse_typed(param, &value).map(Some).map_err(CommandError::InvalidParam);
+    }
+    Ok(None)
+}
+
+/** Check a value against the parameter's static choices (if any).
+    Choices loaded with `choices_from` are not checked, to avoid running the source command. */
+fn validate_choice(param: &ParamDef, value: &str) -> Result<(), String> {
+    if param.choices.is_empty() || param.choices_from.is_some() || param.choices.iter().any(|c| c == value) {
+        return Ok(());
+    }
+    if param.optional && value.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "Invalid value '{}' for parameter '{}'. Expected one of: {}",
+        value,
+        param.name,
+        param.choices.join(", ")
+    ))
+}
+
+/**
+ Present the parameter's choices as a selection list. The default (if it is
+ one of the choices) is pre-highlighted; optional parameters get a leading
+ "skip" entry which yields an empty value.
+*/
+fn prompt_choice(param: &ParamDef, choices: &[String], prompt: &str) -> Result<String, String> {
+    let mut items: Vec<String> = Vec::new();
+    if param.optional {
+        items.push("(skip)".to_string());
+    }
+    let offset = items.len();
+    items.extend(choices.iter().cloned());
+
+    let default_idx = param
+        .default
+        .as_ref()
+        .and_then(|d| choices.iter().position(|c| c == d))
+        .map(|i| i + offset)
+        .unwrap_or(0);
+
+    let selection = Select::new()
+        .with_prompt(prompt)
+        .items(&items)
+        .default(default_idx)
+        .interact()
+        .map_err(|e| format!("Selection for '{}' failed: {}", param.name, e))?;
+
+    if selection < offset {
+        Ok(String::new())
+    } else {
+        Ok(items[selection].clone())
+    }
+}
+
+/** Present the parameter's choices as a multi-selection list with the defaults pre-checked. */
+fn prompt_multi_choice(
+    param: &ParamDef,
+    choices: &[String],
+    defaults: &[String],
+    prompt: &str,
+) -> Result<Vec<String>, String> {
+    let checked: Vec<bool> = choices.iter().map(|c| defaults.contains(c)).collect();
+
+    let selection = MultiSelect::new()
+        .with_prompt(format!("{prompt} (space to select, enter to confirm)"))
+        .items(choices)
+        .defaults(&checked)
+        .interact()
+        .map_err(|e| format!("Selection for '{}' failed: {}", param.name, e))?;
+
+    Ok(selection.into_iter().map(|i| choices[i].clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exit_codes;
+
+    fn overrides(pairs: &[&str]) -> HashMap<String, Vec<String>> {
+        parse_param_overrides(&pairs.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    fn collect(cmd: &CommandDef, pairs: &[&str]) -> Result<HashMap<String, Value>, CommandError> {
+        let (cfg, ctx) = (Config::default(), GlobalContext::default());
+        substitute_parameters(cmd, Some(&overrides(pairs)), false, &ParamSource::new(&cfg, &ctx))
+    }
+
+    fn command(params: Vec<ParamDef>) -> CommandDef {
+        CommandDef { name: "c".to_string(), exec: "echo".to_string(), params, ..Default::default() }
+    }
+
+    #[test]
+    fn finds_variables_in_conditions() {
+        assert_eq!(condition_variables(r#"env == "prod" and not dry_run"#), ["env", "dry_run"]);
+        assert_eq!(condition_variables("params.region == 'eu' or region is defined"), ["params", "region"]);
+        assert_eq!(condition_variables("tags | length > 0 and now(utc=true) != x.y"), ["tags", "x"]);
+        assert_eq!(condition_variables("name is not undefined"), ["name"]);
+    }
+
+    #[test]
+    fn discovers_params_used_only_in_conditions_and_confirm() {
+        let cmd: CommandDef = serde_yaml::from_str(
+            r#"
+name: deploy
+confirm: "Deploy {{ version }} to {{ params.env }}?"
+steps:
+  - exec: echo build
+  - exec: echo push
+    when: push and registry != ""
+"#,
+        )
+        .unwrap();
+        assert_eq!(parameter_names(&cmd), ["version", "push", "registry"]);
     }
 
-    collected
-}
\ No newline at end of file
+    #[test]
+    fn checks_static_choices() {
+        let region = ParamDef {
+            name: "region".to_string(),
+            choices: vec!["eu".to_string(), "us".to_string()],
+            ..Default::default()
+        };
+        assert!(validate_choice(&region, "eu").is_ok());
+        assert!(validate_choice(&region, "ap").unwrap_err().contains("Expected one of: eu, us"));
+    }
+
+    #[test]
+    fn converts_overrides_to_typed_values() {
+        let cmd = command(vec![ParamDef { name: "n".to_string(), kind: ParamType::Int, ..Default::default() }]);
+        assert_eq!(collect(&cmd, &["n=3"]).unwrap()["n"], Value::from(3));
+    }
+
+    #[test]
+    fn invalid_overrides_exit_with_invalid_param() {
+        let cmd = command(vec![
+            ParamDef { name: "n".to_string(), kind: ParamType::Int, max: Some(5.0), ..Default::default() },
+            ParamDef { name: "region".to_string(), choices: vec!["eu".to_string()], ..Default::default() },
+        ]);
+        for pairs in [["n=abc", "region=eu"], ["n=6", "region=eu"], ["n=1", "region=us"]] {
+            let err = collect(&cmd, &pairs).unwrap_err();
+            assert_eq!(err.exit_code(), exit_codes::INVALID_PARAM, "{:?}: {}", pairs, err);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+/** Running a command together with its pre- and post-commands. */
+use crate::commands::arguments::{inherit_parameters, ParamSource};
+use crate::commands::confirm::confirm_run;
+use crate::commands::errors::CommandError;
+use crate::commands::runner::{run_command, run_command_captured, store_capture};
+use crate::commands::utils::{command_path, find_command};
+use crate::config::{CommandDef, Config, GlobalContext};
+use crate::exit_codes;
+use crate::utils::output::{errln, outln};
+use colored::Colorize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Instant;
+
+/**
+ Run a command with its pre- and post-commands and hooks, as in both direct and interactive mode.
+
+ Pre-commands run first, in order, and receive the command's params (`args`);
+ parameters only they use are collected as usual. Output they capture becomes a
+ variable of the command. A failing pre-command stops the command. Afterwards,
+ the `on_success` or `on_failure` hooks run, followed by the post-commands, which
+ run whether the command succeeded or not. These also receive the command's
+ `exit_code` and `duration` (in seconds). All of them may have their own pre- and
+ post-commands, which run in the same way; a command that (indirectly) refers to
+ itself is reported as a cycle. Commands are confirmed first when they need it
+ (see `confirm_run`).
+*/
+pub fn run_command_chain(
+    cmd: &CommandDef,
+    cfg: &Config,
+    ctx: &mut GlobalContext,
+    args: &HashMap<String, Value>,
+    prompt_for_missing: bool,
+) -> Result<(), CommandError> {
+    let name = command_path(&cfg.categories, cmd).unwrap_or_else(|| cmd.name.clone());
+    let mut chain = Chain { cfg, prompt_for_missing };
+    chain.run(cmd, &name, ctx, args, false).map(|_| ())
+}
+
+struct Chain<'a> {
+    cfg: &'a Config,
+    prompt_for_missing: bool,
+}
+
+impl<'a> Chain<'a> {
+    /** Run `cmd` with its pre- and post-commands. With `capture`, the command's stdout is returned. */
+    fn run(
+        &mut self,
+        cmd: &'a CommandDef,
+        name: &str,
+        ctx: &mut GlobalContext,
+        args: &HashMap<String, Value>,
+        capture: bool,
+    ) -> Result<String, CommandError> {
+        // running commands are identified by their path, whatever name they were referred to by
+        let path = command_path(&self.cfg.categories, cmd).unwrap_or_else(|| name.to_string());
+        if ctx.running.contains(&path) {
+            let cycle: Vec<&str> = ctx.running.iter().map(String::as_str).chain([path.as_str()]).collect();
+            return Err(CommandError::Config(format!("Command cycle detected: {}", cycle.join(" -> "))));
+        }
+        // the protected group is confirmed once, for the outermost command
+        confirm_run(cmd, name, self.cfg, ctx, args, ctx.running.is_empty())?;
+        ctx.running.push(path);
+        let inherited_secrets = ctx.secret_params.len();
+        add_secret_params(ctx, cmd);
+
+        let started = Instant::now();
+        let mut args = args.clone();
+        let result = self
+            .run_pre_commands(cmd, ctx, &mut args)
+            .and_then(|_| {
+                if capture {
+                    run_command_captured(cmd, self.cfg, ctx, &args)
+                } else {
+                    run_command(cmd, self.cfg, ctx, &args).map(|_| String::new())
+                }
+            });
+
+        // hooks see how the command went
+        let exit_code = result.as_ref().map_or_else(CommandError::exit_code, |_| exit_codes::SUCCESS);
+        args.insert("exit_code".to_string(), Value::from(exit_code));
+        args.insert("duration".to_string(), Value::from(started.elapsed().as_millis() as f64 / 1000.0));
+        let result = self.run_hooks(cmd, ctx, &args, result);
+
+        ctx.running.pop();
+        ctx.secret_params.truncate(inherited_secrets);
+        result
+    }
+
+    /** Run the pre-commands in order, storing their captured output in `args`. */
+    fn run_pre_commands(
+        &mut self,
+        cmd: &'a CommandDef,
+        ctx: &mut GlobalContext,
+        args: &mut HashMap<String, Value>,
+    ) -> Result<(), CommandError> {
+        for pre in &cmd.pre_command {
+            let stdout = self.run_related("pre-command", pre.command(), ctx, args, pre.capture().is_some())?;
+            if let Some(capture) = pre.capture() {
+                store_capture(args, capture, &stdout, ctx.dry_run)?;
+            }
+        }
+        Ok(())
+    }
+
+    /** Run the `on_success` or `on_failure` hooks, then all post-commands. Each of them runs
+        even if an earlier one failed; the command's own failure takes precedence over theirs. */
+    fn run_hooks(
+        &mut self,
+        cmd: &'a CommandDef,
+        ctx: &mut GlobalContext,
+        args: &HashMap<String, Value>,
+        result: Result<String, CommandError>,
+    ) -> Result<String, CommandError> {
+        let (kind, hooks) = match result {
+            Ok(_) => ("on-success", &cmd.on_success),
+            Err(_) => ("on-failure", &cmd.on_failure),
+        };
+        let hooks = hooks
+            .iter()
+            .map(|name| (kind, name))
+            .chain(cmd.post_command.iter().map(|name| ("post-command", name)));
+
+        let mut result = result;
+        for (kind, name) in hooks {
+            if let Err(e) = self.run_related(kind, name, ctx, args, false)
+                && result.is_ok()
+            {
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /** Resolve and run a pre- or post-command with the parent's args. Its errors are
+        reported here and returned as a plain failure with the same exit code. */
+    fn run_related(
+        &mut self,
+        kind: &str,
+        name: &str,
+        ctx: &mut GlobalContext,
+        parent_args: &HashMap<String, Value>,
+        capture: bool,
+    ) -> Result<String, CommandError> {
+        let cfg = self.cfg;
+        let result = match find_command(&cfg.categories, name) {
+            Some(related) => {
+                if !ctx.print_only {
+                    outln!(ctx.output_label.as_deref(), "{}", format!("▶ {}: {}", kind, name).bold());
+                }
+                inherit_parameters(related, parent_args, self.prompt_for_missing, &ParamSource::new(cfg, ctx))
+                    .and_then(|args| self.run(related, name, ctx, &args, capture))
+            }
+            None => Err(CommandError::Config("not found in the catalogue".to_string())),
+        };
+
+        result.map_err(|e| match e {
+            CommandError::Failed(_) => e,
+            e => {
+                errln!(ctx.output_label.as_deref(), "{} '{}': {}", capitalize(kind), name, e);
+                CommandError::Failed(e.exit_code())
+            }
+        })
+    }
+}
+
+/** Mark the command's secret params as secret for the commands it runs (they receive its args). */
+fn add_secret_params(ctx: &mut GlobalContext, cmd: &CommandDef) {
+    for param in cmd.params.iter().filter(|p| p.secret) {
+        if !ctx.secret_params.contains(&param.name) {
+            ctx.secret_params.push(param.name.clone());
+        }
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::runner::preview_command;
+    use crate::config::Category;
+
+    #[test]
+    fn redacts_secrets_inherited_through_a_chain() {
+        let categories: Vec<Category> = serde_yaml::from_str(
+            r#"
+- category: t
+  commands:
+    - name: main
+      exec: echo main {{ token }}
+      params:
+        - name: token
+          secret: true
+      post_command: [t.post]
+    - name: post
+      exec: echo post {{ token }}
+"#,
+        )
+        .unwrap();
+        let cfg = Config { categories, ..Default::default() };
+        let main = find_command(&cfg.categories, "t.main").unwrap();
+        let post = find_command(&cfg.categories, "t.post").unwrap();
+        let args = HashMap::from([("token".to_string(), Value::from("hunter2"))]);
+
+        let mut ctx = GlobalContext::default();
+        assert_eq!(preview_command(post, &cfg, &ctx, &args).unwrap(), ["echo post hunter2"]);
+
+        add_secret_params(&mut ctx, main);
+        assert_eq!(preview_command(post, &cfg, &ctx, &args).unwrap(), ["echo post ****"]);
+    }
+}
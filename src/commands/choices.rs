@@ -0,0 +1,175 @@
+/** Resolution of parameter choices from static lists and shell commands. */
+use crate::config::{ChoicesFrom, ParamDef};
+use crate::utils::shell::{capture_shell_command, ExecOptions};
+use log::debug;
+use serde_json::Value;
+
+/** True if the parameter is answered by selecting from a list of choices. */
+pub fn has_choices(param: &ParamDef) -> bool {
+    !param.choices.is_empty() || param.choices_from.is_some()
+}
+
+/**
+ Build the full list of choices for a parameter: the static `choices` first,
+ followed by any values produced by `choices_from`, run with the given env, shell
+ and working directory. Duplicates are dropped.
+*/
+pub fn resolve_choices(param: &ParamDef, exec_opts: &ExecOptions) -> Result<Vec<String>, String> {
+    let mut choices = param.choices.clone();
+
+    if let Some(source) = &param.choices_from {
+        for choice in choices_from_command(source, exec_opts)? {
+            if !choices.contains(&choice) {
+                choices.push(choice);
+            }
+        }
+    }
+
+    if choices.is_empty() {
+        return Err(format!("No choices available for parameter '{}'", param.name));
+    }
+    Ok(choices)
+}
+
+/** Run the source command and turn its output into a list of choices. */
+fn choices_from_command(source: &ChoicesFrom, exec_opts: &ExecOptions) -> Result<Vec<String>, String> {
+    debug!("Loading choices from `{}`", source.command());
+    let stdout = capture_shell_command(source.command(), exec_opts)?;
+
+    match source.json_path() {
+        Some(path) => {
+            let json: Value = serde_json::from_str(&stdout)
+                .map_err(|e| format!("Failed to parse output of `{}` as JSON: {}", source.command(), e))?;
+            Ok(select_json_path(&json, path)?
+                .into_iter()
+                .map(json_to_choice)
+                .filter(|s| !s.is_empty())
+                .collect())
+        }
+        None => Ok(stdout
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect()),
+    }
+}
+
+/** Strings are used as-is, other JSON values use their JSON representation. */
+fn json_to_choice(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/**
+ Evaluate a simple JSON path such as `items[*].metadata.name` or `$.value[0].id`.
+ Supported segments: object keys, array indices (`[n]`) and wildcards (`[*]`).
+ A trailing array is flattened so its elements become individual choices.
+*/
+fn select_json_path<'a>(root: &'a Value, path: &str) -> Result<Vec<&'a Value>, String> {
+    let path = path.trim().trim_start_matches('$').trim_start_matches('.');
+    let mut current: Vec<&Value> = vec![root];
+
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let (key, indices) = split_segment(segment)
+            .ok_or_else(|| format!("Invalid JSON path segment '{}' in '{}'", segment, path))?;
+
+        if !key.is_empty() {
+            current = current.into_iter().filter_map(|v| v.get(key)).collect();
+        }
+
+        for index in indices {
+            current = match index {
+                "*" => current
+                    .into_iter()
+                    .filter_map(Value::as_array)
+                    .flatten()
+                    .collect(),
+                n => {
+                    let n: usize = n
+                        .parse()
+                        .map_err(|_| format!("Invalid array index '{}' in '{}'", n, path))?;
+                    current.into_iter().filter_map(|v| v.get(n)).collect()
+                }
+            };
+        }
+    }
+
+    // Flatten a trailing array into its elements
+    Ok(current
+        .into_iter()
+        .flat_map(|v| match v {
+            Value::Array(items) => items.iter().collect(),
+            other => vec![other],
+        })
+        .collect())
+}
+
+/** Split `name[0][*]` into ("name", ["0", "*"]). */
+fn split_segment(segment: &str) -> Option<(&str, Vec<&str>)> {
+    let (key, mut rest) = match segment.find('[') {
+        Some(pos) => (&segment[..pos], &segment[pos..]),
+        None => (segment, ""),
+    };
+
+    let mut indices = Vec::new();
+    while !rest.is_empty() {
+        let end = rest.find(']')?;
+        if !rest.starts_with('[') {
+            return None;
+        }
+        indices.push(rest[1..end].trim());
+        rest = &rest[end + 1..];
+    }
+    Some((key, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn select(json: &Value, path: &str) -> Result<Vec<String>, String> {
+        select_json_path(json, path).map(|values| values.into_iter().map(json_to_choice).collect())
+    }
+
+    #[test]
+    fn selects_keys_through_wildcards() {
+        let json = json!({ "items": [
+            { "metadata": { "name": "api" } },
+            { "metadata": { "name": "worker" } },
+        ] });
+        assert_eq!(select(&json, "items[*].metadata.name"), Ok(vec!["api".to_string(), "worker".to_string()]));
+    }
+
+    #[test]
+    fn selects_array_indices_from_the_root() {
+        let json = json!({ "value": [{ "id": 1 }, { "id": 2 }] });
+        assert_eq!(select(&json, "$.value[0].id"), Ok(vec!["1".to_string()]));
+    }
+
+    #[test]
+    fn selects_from_a_top_level_array() {
+        let json = json!([{ "name": "rg-dev" }, { "name": "rg-prod" }]);
+        assert_eq!(select(&json, "[*].name"), Ok(vec!["rg-dev".to_string(), "rg-prod".to_string()]));
+    }
+
+    #[test]
+    fn flattens_a_trailing_array() {
+        let json = json!({ "tags": ["a", "b"] });
+        assert_eq!(select(&json, "tags"), Ok(vec!["a".to_string(), "b".to_string()]));
+    }
+
+    #[test]
+    fn rejects_malformed_segments() {
+        let json = json!({ "a": [1] });
+        assert!(select(&json, "a[0").is_err());
+        assert!(select(&json, "a[x]").is_err());
+        assert_eq!(split_segment("a[0"), None);
+        assert_eq!(split_segment("a[0]b"), None);
+        assert_eq!(split_segment("a[0][*]"), Some(("a", vec!["0", "*"])));
+    }
+}
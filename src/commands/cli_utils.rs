@@ -1,11 +1,16 @@
-use dialoguer::{Select, console::Term};
+use dialoguer::{Password, Select, console::Term};
 use colored::Colorize;
+use crate::cli::SecretAction;
 use crate::config::{CommandDef, Config, GlobalContext};
 use crate::interactive::switchers::switch_subscription;
+use crate::secrets::{backend_for, SecretError};
+
+/** Shown by group-related options when there is no params file */
+pub const NO_GROUPS: &str = "No param groups configured: add a scli.params.yaml file with `groups` to use them.";
 
 pub fn handle_switch_subscription(cfg: &Config, ctx: &mut GlobalContext) {
     if cfg.params.is_empty() {
-        println!("No groups configured.");
+        println!("{}", NO_GROUPS);
         return;
     }
 
@@ -30,6 +35,41 @@ pub fn handle_switch_subscription(cfg: &Config, ctx: &mut GlobalContext) {
     }
 }
 
+/** Handle `scli secret set/get/list/rm` against the configured secret backend. */
+pub fn handle_secret_command(cfg: &Config, action: &SecretAction, allow_prompts: bool) -> Result<(), SecretError> {
+    let backend = backend_for(cfg, allow_prompts);
+
+    match action {
+        SecretAction::Set { key, value } => {
+            let value = match value {
+                Some(v) => v.clone(),
+                None => Password::new()
+                    .with_prompt(format!("Value for secret '{}'", key))
+                    .interact()
+                    .map_err(|e| SecretError::Store(format!("Failed to read value: {}", e)))?,
+            };
+            backend.set(key, &value)?;
+            println!("Saved secret '{}'", key);
+        }
+        SecretAction::Get { key } => {
+            let value = backend.get(key)?.ok_or_else(|| SecretError::NotFound(key.clone()))?;
+            println!("{}", value);
+        }
+        SecretAction::List => {
+            for key in backend.list()? {
+                println!("{}", key);
+            }
+        }
+        SecretAction::Rm { key } => {
+            if !backend.remove(key)? {
+                return Err(SecretError::NotFound(key.clone()));
+            }
+            println!("Removed secret '{}'", key);
+        }
+    }
+    Ok(())
+}
+
 /** Build ARGS column for a command, wrapping optional args in [brackets]. */
 pub fn args_column(cmd: &CommandDef) -> String {
     if cmd.params.is_empty() {
@@ -187,7 +227,12 @@ NAME/DESCRIPTION/ARGS table format as interactive mode.
 */
 pub fn list_commands(cfg: &Config) {
     for cat in &cfg.categories {
-        println!("\n{}", cat.category.bold().on_blue());
+        // commands outside of any category are listed without a heading
+        if !cat.category.trim().is_empty() {
+            println!("\n{}", cat.category.bold().on_blue());
+        } else {
+            println!();
+        }
 
         let cat_prefix = normalize_name(&cat.category);
         if !cat.commands.is_empty() {
@@ -206,4 +251,23 @@ pub fn list_commands(cfg: &Config) {
             }
         }
     }
-}
\ No newline at end of file
+}
+/** Handle `scli config sources`: the files read, lowest precedence first, and the file
+    each command and param came from. */
+pub fn print_config_sources(cfg: &Config) {
+    println!("{}", "Files (lowest precedence first):".bold());
+    for file in &cfg.sources.files {
+        println!("  {}", file.display());
+    }
+
+    for (heading, sources) in [("Commands:", &cfg.sources.commands), ("Params:", &cfg.sources.params)] {
+        println!("\n{}", heading.bold());
+        if sources.is_empty() {
+            println!("  (none)");
+        }
+        let name_w = sources.keys().map(|k| k.len()).max().unwrap_or(0);
+        for (name, file) in sources {
+            println!("  {:<name_w$}  {}", name, file.display().to_string().bright_black());
+        }
+    }
+}
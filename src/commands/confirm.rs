@@ -0,0 +1,74 @@
+/** Confirmation before running commands marked `confirm` or in a protected group. */
+use crate::commands::errors::CommandError;
+use crate::commands::runner::{active_group, preview_command, preview_template};
+use crate::config::{CommandDef, Config, GlobalContext};
+use colored::Colorize;
+use dialoguer::{Confirm, Input};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/**
+ Ask before running `cmd` if it is marked `confirm`, or if `check_group` is set and
+ the active group is `protected`. Shows the command, as rendered, and the active group (if any).
+ Protected groups require typing the group name; `confirm` alone asks yes/no. Dry runs
+ and `--yes` skip the question; with `--no-input` it is an error.
+*/
+pub fn confirm_run(
+    cmd: &CommandDef,
+    name: &str,
+    cfg: &Config,
+    ctx: &GlobalContext,
+    args: &HashMap<String, Value>,
+    check_group: bool,
+) -> Result<(), CommandError> {
+    let question = cmd.confirm.as_ref().and_then(|c| c.question());
+    let protected = check_group && active_group(cfg, ctx)?.protected;
+    if (question.is_none() && !protected) || ctx.dry_run || ctx.assume_yes {
+        return Ok(());
+    }
+
+    let question = question.map(|q| preview_template(q, cfg, ctx, args)).transpose()?;
+    let group = ctx.current_group.clone().unwrap_or_default();
+    if ctx.no_input {
+        let reason = if protected { format!("group '{}' is protected", group) } else { "it asks for confirmation".to_string() };
+        return Err(CommandError::NotConfirmed(format!(
+            "Not running '{}': {}. Use --yes to run it without confirmation.",
+            name, reason
+        )));
+    }
+
+    println!("{} {}", "Command:".bold(), name);
+    if protected {
+        println!("{} {} {}", "Group:".bold(), group, "(protected)".red().bold());
+    } else if !cfg.params.is_empty() && !group.is_empty() {
+        println!("{} {}", "Group:".bold(), group);
+    }
+    for line in preview_command(cmd, cfg, ctx, args)? {
+        println!("{} {}", "$".blue().bold(), line.as_str().bright_black());
+    }
+
+    let confirmed = if protected {
+        let prompt = match question {
+            Some(question) => format!("{} Type the group name '{}' to confirm", question, group),
+            None => format!("Type the group name '{}' to confirm", group),
+        };
+        let typed: String = Input::new()
+            .with_prompt(prompt)
+            .allow_empty(true)
+            .interact_text()
+            .unwrap_or_default();
+        typed.trim() == group
+    } else {
+        Confirm::new()
+            .with_prompt(question.unwrap_or_default())
+            .default(false)
+            .interact()
+            .unwrap_or(false)
+    };
+
+    if confirmed {
+        Ok(())
+    } else {
+        Err(CommandError::NotConfirmed(format!("Not running '{}': not confirmed.", name)))
+    }
+}
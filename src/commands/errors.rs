@@ -0,0 +1,76 @@
+/** Errors raised while preparing and running a command. */
+use crate::exit_codes;
+use crate::utils::string::format_duration;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("Unknown command: {0}")]
+    UnknownCommand(String),
+
+    #[error("{0}")]
+    Usage(String),
+
+    #[error("Missing required parameters:\n{}", format_missing(.0))]
+    MissingParams(Vec<MissingParam>),
+
+    #[error("{0}")]
+    InvalidParam(String),
+
+    #[error("{0}")]
+    Config(String),
+
+    #[error("Failed to render: {0}")]
+    Render(String),
+
+    #[error("Failed to execute: {0}")]
+    Execute(String),
+
+    #[error("{0}")]
+    NotConfirmed(String),
+
+    #[error("Command failed with exit code {0}")]
+    Failed(i32),
+
+    #[error("Command timed out after {}", format_duration(*.0))]
+    TimedOut(Duration),
+}
+
+/** A required parameter without a value, with the prompt it would have been asked with. */
+#[derive(Debug, Clone)]
+pub struct MissingParam {
+    pub name: String,
+    pub prompt: String,
+}
+
+fn format_missing(missing: &[MissingParam]) -> String {
+    missing
+        .iter()
+        .map(|m| {
+            if m.prompt.trim().is_empty() {
+                format!("  - {}", m.name)
+            } else {
+                format!("  - {}: {}", m.name, m.prompt.trim())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl CommandError {
+    /** Exit code for the process; child failures pass the child's code through. */
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CommandError::UnknownCommand(_) | CommandError::Usage(_) => exit_codes::USAGE,
+            CommandError::MissingParams(_) => exit_codes::MISSING_PARAMS,
+            CommandError::InvalidParam(_) => exit_codes::INVALID_PARAM,
+            CommandError::Config(_) => exit_codes::CONFIG,
+            CommandError::Render(_) => exit_codes::RENDER,
+            CommandError::Execute(_) => exit_codes::EXECUTE,
+            CommandError::NotConfirmed(_) => exit_codes::NOT_CONFIRMED,
+            CommandError::Failed(code) => *code,
+            CommandError::TimedOut(_) => exit_codes::TIMEOUT,
+        }
+    }
+}
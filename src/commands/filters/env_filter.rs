@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+use tera::{Value, Filter, Function, Result as TeraResult, Error};
+
+/** Look up an environment variable, falling back to the `default` argument if given. */
+fn lookup(name: &str, args: &HashMap<String, Value>) -> TeraResult<Value> {
+    match std::env::var(name) {
+        Ok(value) => Ok(Value::from(value)),
+        Err(_) => match args.get("default") {
+            Some(default) => Ok(default.clone()),
+            None => Err(Error::msg(format!("Environment variable '{}' is not set", name))),
+        },
+    }
+}
+
+/** Tera function: `{{ env(name="USER", default="me") }}` */
+pub struct EnvFunction;
+
+impl Function for EnvFunction {
+    fn call(&self, args: &HashMap<String, Value>) -> TeraResult<Value> {
+        let name = args
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| Error::msg("Function `env` requires a `name` argument"))?;
+        lookup(name, args)
+    }
+}
+
+/** Tera filter: `{{ "USER" | env(default="me") }}` */
+pub struct EnvFilter;
+
+impl Filter for EnvFilter {
+    fn filter(&self, value: &Value, args: &HashMap<String, Value>) -> TeraResult<Value> {
+        let name = tera::try_get_value!("env", "value", String, value);
+        lookup(&name, args)
+    }
+}
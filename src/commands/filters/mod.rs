@@ -1 +1,3 @@
-pub mod param_filter;
\ No newline at end of file
+pub mod env_filter;
+pub mod param_filter;
+pub mod secret_filter;
\ No newline at end of file
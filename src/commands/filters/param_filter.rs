@@ -3,29 +3,40 @@ use tera::{Value, Filter, Result as TeraResult, Error};
 use crate::commands::kubernetes::namespace::get_user_namespace_choice;
 
 pub struct ParamFilter {
-    arg_overrides: HashMap<String, String>,
+    arg_overrides: HashMap<String, Value>,
+    allow_prompts: bool,
 }
 
 impl ParamFilter {
-    pub fn new(args: &HashMap<String, String>) -> Self {
+    pub fn new(args: &HashMap<String, Value>, allow_prompts: bool) -> Self {
         Self {
             arg_overrides: args.clone(),
+            allow_prompts,
         }
     }
 }
 
 impl Filter for ParamFilter {
     fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> TeraResult<Value> {
+        let name = tera::try_get_value!("param", "value", String, value);
+
         // If an override is present, return it
-        if let Some(arg_override) = self.arg_overrides.get(value.as_str().unwrap()) {
-            return Ok(Value::from(arg_override.clone()));
+        if let Some(arg_override) = self.arg_overrides.get(&name) {
+            return Ok(arg_override.clone());
+        }
+
+        if !self.allow_prompts {
+            return Err(Error::msg(format!(
+                "No value provided for interactive param '{}' (pass it with --param {}=...)",
+                name, name
+            )));
         }
 
         // Else use context
-        match tera::try_get_value!("param", "value", String, value).as_str() {
+        match name.as_str() {
             "namespace" => {
                 let ns_arg = get_user_namespace_choice()
-                    .map_err(|e| Error::msg(e))?;
+                    .map_err(Error::msg)?;
                 Ok(Value::from(ns_arg))
             },
             other => {
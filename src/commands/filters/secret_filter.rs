@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tera::{Value, Filter, Result as TeraResult, Error};
+use crate::secrets::SecretBackend;
+
+/** Tera filter resolving a secret by name from the configured backend: `{{ "mysecret" | secret }}` */
+pub struct SecretFilter {
+    backend: Arc<dyn SecretBackend>,
+    revealed: Arc<Mutex<Vec<String>>>,
+}
+
+impl SecretFilter {
+    /** Every value looked up is also recorded in `revealed`, so it can be redacted from output. */
+    pub fn new(backend: Arc<dyn SecretBackend>, revealed: Arc<Mutex<Vec<String>>>) -> Self {
+        Self { backend, revealed }
+    }
+}
+
+impl Filter for SecretFilter {
+    fn filter(&self, value: &Value, _args: &HashMap<String, Value>) -> TeraResult<Value> {
+        let key = tera::try_get_value!("secret", "value", String, value);
+
+        let secret = self.backend
+            .get(&key)
+            .map_err(|e| Error::msg(e.to_string()))?
+            .ok_or_else(|| Error::msg(format!("Secret '{}' not found", key)))?;
+
+        if let Ok(mut revealed) = self.revealed.lock() {
+            revealed.push(secret.clone());
+        }
+        Ok(Value::from(secret))
+    }
+}
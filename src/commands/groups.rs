@@ -0,0 +1,71 @@
+/** Running a command once per param group (`--groups`, `--all-groups`). */
+use crate::commands::arguments::{inherit_parameters, ParamSource};
+use crate::commands::cli_utils::NO_GROUPS;
+use crate::commands::errors::CommandError;
+use crate::commands::jobs::{report_jobs, run_command_jobs, CommandJob};
+use crate::config::{CommandDef, Config, GlobalContext};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/** The groups to run in: the named ones, checked against the params file, or all of them (sorted). */
+pub fn resolve_groups(cfg: &Config, names: &[String], all: bool) -> Result<Vec<String>, CommandError> {
+    if cfg.params.is_empty() {
+        return Err(CommandError::Config(NO_GROUPS.to_string()));
+    }
+    let mut available: Vec<String> = cfg.params.keys().cloned().collect();
+    available.sort();
+    if all {
+        return Ok(available);
+    }
+
+    let mut groups: Vec<String> = Vec::new();
+    for name in names.iter().map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        if !cfg.params.contains_key(name) {
+            return Err(CommandError::InvalidParam(format!(
+                "Unknown group '{}'. Available groups: {}",
+                name,
+                available.join(", ")
+            )));
+        }
+        if !groups.iter().any(|g| g == name) {
+            groups.push(name.to_string());
+        }
+    }
+    if groups.is_empty() {
+        return Err(CommandError::Usage(format!(
+            "No group names given with --groups. Available groups: {}",
+            available.join(", ")
+        )));
+    }
+    Ok(groups)
+}
+
+/**
+ Run a command (with its pre- and post-commands) once in each group, with the same
+ `args` but each group's own params, then print a table of the results (see
+ `run_command_jobs`). The exit code is that of the first failed group.
+
+ Params with `choices_from` are left out of `args` and collected here for each group
+ before any group runs, as their choices depend on the group's env.
+*/
+pub fn run_in_groups(
+    cmd: &CommandDef,
+    cfg: &Config,
+    ctx: &GlobalContext,
+    args: &HashMap<String, Value>,
+    groups: &[String],
+    parallel: bool,
+    prompt_for_missing: bool,
+) -> Result<(), CommandError> {
+    let mut jobs: Vec<CommandJob> = Vec::new();
+    for group in groups {
+        let mut group_ctx = ctx.clone();
+        group_ctx.current_group = Some(group.clone());
+        let source = ParamSource { show_group: true, ..ParamSource::new(cfg, &group_ctx) };
+        let group_args = inherit_parameters(cmd, args, prompt_for_missing, &source)?;
+        jobs.push(CommandJob { name: group.clone(), cmd, ctx: group_ctx, args: group_args });
+    }
+
+    let results = run_command_jobs("group", &jobs, cfg, parallel, prompt_for_missing);
+    report_jobs("group", &results, ctx)
+}
@@ -0,0 +1,156 @@
+/** Running several commands (or one command in several groups) one after the other or in parallel. */
+use crate::commands::chain::run_command_chain;
+use crate::commands::confirm::confirm_run;
+use crate::commands::errors::CommandError;
+use crate::commands::utils::command_path;
+use crate::config::{CommandDef, Config, GlobalContext};
+use crate::exit_codes;
+use crate::utils::output::{errln, job_labels, outln};
+use crate::utils::string::format_elapsed;
+use colored::Colorize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/** A command to run as one of several jobs, with its own context (e.g. group) and params. */
+pub struct CommandJob<'a> {
+    /** Shown in headers, output labels and the results table */
+    pub name: String,
+    pub cmd: &'a CommandDef,
+    pub ctx: GlobalContext,
+    pub args: HashMap<String, Value>,
+}
+
+/** How a job ended. */
+#[derive(Debug, Clone)]
+pub struct JobResult {
+    pub name: String,
+    pub exit_code: i32,
+    pub duration: Duration,
+}
+
+/**
+ Run each job's command with its pre- and post-commands. All jobs run, whether others
+ fail or not; errors other than plain failures are reported here.
+
+ Sequential jobs are introduced by a `▶ kind: name` header. Parallel jobs run on their
+ own threads and every line of their output is prefixed with a coloured label
+ (`name |`). They cannot prompt, so each is confirmed (if needed) before any of them
+ starts, and a job that is not confirmed is not run. With `--print`, jobs always run one
+ after the other without labels, so only the raw commands are written, in order.
+*/
+pub fn run_command_jobs(
+    kind: &str,
+    jobs: &[CommandJob],
+    cfg: &Config,
+    parallel: bool,
+    prompt_for_missing: bool,
+) -> Vec<JobResult> {
+    let parallel = parallel && !jobs.iter().any(|job| job.ctx.print_only);
+    let mut results: Vec<Option<JobResult>> = vec![None; jobs.len()];
+    let mut runnable: Vec<usize> = Vec::new();
+    for (i, job) in jobs.iter().enumerate() {
+        if parallel {
+            let name = command_path(&cfg.categories, job.cmd).unwrap_or_else(|| job.cmd.name.clone());
+            if let Err(e) = confirm_run(job.cmd, &name, cfg, &job.ctx, &job.args, job.ctx.running.is_empty()) {
+                errln!(job.ctx.output_label.as_deref(), "{}", e);
+                results[i] = Some(JobResult { name: job.name.clone(), exit_code: e.exit_code(), duration: Duration::ZERO });
+                continue;
+            }
+        }
+        runnable.push(i);
+    }
+
+    let run_job = |i: usize, label: Option<&str>| {
+        let job = &jobs[i];
+        let mut ctx = job.ctx.clone();
+        if let Some(label) = label {
+            // nested parallel runs keep the outer label
+            ctx.output_label = Some(match &ctx.output_label {
+                Some(outer) => format!("{} {}", outer, label),
+                None => label.to_string(),
+            });
+            ctx.assume_yes = true;
+            ctx.no_input = true;
+        } else if !ctx.print_only {
+            outln!(ctx.output_label.as_deref(), "{}", format!("▶ {}: {}", kind, job.name).bold());
+        }
+
+        let started = Instant::now();
+        let result = run_command_chain(job.cmd, cfg, &mut ctx, &job.args, prompt_for_missing && label.is_none());
+        if let Err(e) = &result
+            && !matches!(e, CommandError::Failed(_))
+        {
+            errln!(ctx.output_label.as_deref(), "{}", e);
+        }
+        JobResult {
+            name: job.name.clone(),
+            exit_code: result.map_or_else(|e| e.exit_code(), |_| exit_codes::SUCCESS),
+            duration: started.elapsed(),
+        }
+    };
+
+    if parallel {
+        let names: Vec<String> = runnable.iter().map(|&i| jobs[i].name.clone()).collect();
+        let labels = job_labels(&names);
+        let run_job = &run_job;
+        thread::scope(|scope| {
+            let handles: Vec<_> = runnable
+                .iter()
+                .zip(&labels)
+                .map(|(&i, label)| (i, scope.spawn(move || run_job(i, Some(label)))))
+                .collect();
+            for (i, handle) in handles {
+                results[i] = Some(handle.join().unwrap_or_else(|_| JobResult {
+                    name: jobs[i].name.clone(),
+                    exit_code: exit_codes::FAILURE,
+                    duration: Duration::ZERO,
+                }));
+            }
+        });
+    } else {
+        for i in runnable {
+            results[i] = Some(run_job(i, None));
+        }
+    }
+
+    results.into_iter().flatten().collect()
+}
+
+/**
+ Print a table of the jobs with their exit code and duration (except for dry runs) and
+ return the combined result: the exit code of the first failed job, as a plain failure.
+*/
+pub fn report_jobs(kind: &str, results: &[JobResult], ctx: &GlobalContext) -> Result<(), CommandError> {
+    if !ctx.dry_run {
+        print_job_results(kind, results);
+    }
+    match combined_exit_code(results) {
+        exit_codes::SUCCESS => Ok(()),
+        code => Err(CommandError::Failed(code)),
+    }
+}
+
+fn print_job_results(kind: &str, results: &[JobResult]) {
+    let heading = kind.to_uppercase();
+    let name_w = results.iter().map(|r| r.name.chars().count()).max().unwrap_or(0).max(heading.len());
+    let code_w = "EXIT CODE".len();
+
+    println!();
+    println!("{}", format!("{:<name_w$}  {:<code_w$}  DURATION", heading, "EXIT CODE").bold());
+    for result in results {
+        let code = format!("{:<code_w$}", result.exit_code);
+        let code = if result.exit_code == exit_codes::SUCCESS { code.green() } else { code.red() };
+        println!("{:<name_w$}  {}  {}", result.name, code, format_elapsed(result.duration));
+    }
+}
+
+/** Exit code of a set of jobs: that of the first failed job, or success. */
+pub fn combined_exit_code(results: &[JobResult]) -> i32 {
+    results
+        .iter()
+        .map(|r| r.exit_code)
+        .find(|&code| code != exit_codes::SUCCESS)
+        .unwrap_or(exit_codes::SUCCESS)
+}
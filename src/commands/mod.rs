@@ -1,11 +1,19 @@
 /** Command execution modules */
 pub mod runner;
+pub mod chain;
+pub mod confirm;
+pub mod groups;
+pub mod jobs;
+pub mod errors;
 pub mod arguments;
+pub mod choices;
+pub mod validation;
 pub mod utils;
 pub mod render;
 mod filters;
 mod kubernetes;
 pub mod cli_utils;
 
-pub use runner::*;
+pub use errors::*;
+pub use chain::*;
 pub use utils::*;
\ No newline at end of file
@@ -1,16 +1,45 @@
+use crate::commands::filters::env_filter::{EnvFilter, EnvFunction};
 use crate::commands::filters::param_filter::ParamFilter;
+use crate::commands::filters::secret_filter::SecretFilter;
 use crate::config::{CommandDef, UserParams};
+use crate::secrets::SecretBackend;
 use std::collections::HashMap;
-use tera::{Context, Tera};
+use std::sync::{Arc, Mutex};
+use tera::{Context, Tera, Value};
 
+/** Settings for rendering a command template. */
+pub struct RenderOptions {
+    /** Interactive filters (e.g. `i_param`) may prompt the user */
+    pub allow_prompts: bool,
+    /** Backend used by the `secret` filter */
+    pub secrets: Arc<dyn SecretBackend>,
+    /** Secret values looked up while rendering, so they can be redacted */
+    pub revealed: Arc<Mutex<Vec<String>>>,
+}
+
+/** Render a command's `exec` template. */
 pub fn render_cmd(
     cmd: &CommandDef,
     params: &UserParams,
-    args: &HashMap<String, String>,
+    args: &HashMap<String, Value>,
+    opts: &RenderOptions,
+) -> Result<String, tera::Error> {
+    render_template(&cmd.exec, params, args, opts)
+}
+
+/** Render any command template (exec, env values, cwd) with the group params and collected args. */
+pub fn render_template(
+    template: &str,
+    params: &UserParams,
+    args: &HashMap<String, Value>,
+    opts: &RenderOptions,
 ) -> Result<String, tera::Error> {
     let mut tera = Tera::default();
-    tera.add_raw_template("cmd", cmd.exec.as_str())?;
-    tera.register_filter("i_param", ParamFilter::new(args));
+    tera.add_raw_template("cmd", template)?;
+    tera.register_filter("i_param", ParamFilter::new(args, opts.allow_prompts));
+    tera.register_filter("secret", SecretFilter::new(opts.secrets.clone(), opts.revealed.clone()));
+    tera.register_filter("env", EnvFilter);
+    tera.register_function("env", EnvFunction);
 
     // create context
     let mut context = Context::new();
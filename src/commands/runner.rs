@@ -1,34 +1,559 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::thread;
+use std::time::{Duration, Instant};
 use colored::Colorize;
-use crate::config::{Config, CommandDef, GlobalContext, UserParams};
-use crate::commands::render::render_cmd;
-use crate::utils::shell::execute_shell_command;
+use log::debug;
+use regex::Regex;
+use serde_json::Value;
+use crate::config::{Capture, Config, CommandDef, GlobalContext, StepDef, UserParams};
+use crate::commands::arguments::{inherit_parameters, ParamSource};
+use crate::commands::errors::CommandError;
+use crate::commands::jobs::{combined_exit_code, run_command_jobs, CommandJob};
+use crate::commands::utils::find_command;
+use crate::commands::render::{render_cmd, render_template, RenderOptions};
+use crate::exit_codes;
+use crate::secrets::backend_for;
+use crate::utils::output::{errln, outln};
+use crate::utils::shell::{execute_capturing_stdout, execute_shell_command, ExecOptions, ExecStatus};
+use crate::utils::string::{expand_home, format_duration, format_elapsed, redact};
+
+/** A command with its active group, template variables and rendered process options. */
+struct Invocation<'a> {
+    cmd: &'a CommandDef,
+    cfg: &'a Config,
+    group: &'a UserParams,
+    /** Collected args, plus output captured by earlier steps */
+    args: HashMap<String, Value>,
+    opts: RenderOptions,
+    exec_opts: ExecOptions,
+}
+
+/** How a step of a multi-step command ended. */
+enum StepOutcome {
+    Succeeded(Duration),
+    Failed { code: i32, duration: Duration, continued: bool },
+    /** Its `when` condition was false */
+    Skipped,
+    /** An earlier step failed */
+    NotRun,
+}
 
 pub fn run_command(
     cmd: &CommandDef,
     cfg: &Config,
     ctx: &mut GlobalContext,
-    args: &HashMap<String, String>,
-) -> Result<(), String> {
+    args: &HashMap<String, Value>,
+) -> Result<(), CommandError> {
+    run(cmd, cfg, ctx, args, false).map(|_| ())
+}
+
+/** Run a command like `run_command`, but capture and return its stdout (of all steps) instead of showing it. */
+pub fn run_command_captured(
+    cmd: &CommandDef,
+    cfg: &Config,
+    ctx: &mut GlobalContext,
+    args: &HashMap<String, Value>,
+) -> Result<String, CommandError> {
+    run(cmd, cfg, ctx, args, true)
+}
+
+fn run(
+    cmd: &CommandDef,
+    cfg: &Config,
+    ctx: &GlobalContext,
+    args: &HashMap<String, Value>,
+    capture: bool,
+) -> Result<String, CommandError> {
+
+    let current_config = active_group(cfg, ctx)?;
+
+    let opts = RenderOptions {
+        allow_prompts: !ctx.no_input,
+        secrets: backend_for(cfg, !ctx.no_input),
+        revealed: Default::default(),
+    };
+    let mut exec_opts = exec_options(cmd, current_config, args, &opts, false)?;
+    exec_opts.shell = cmd.shell.clone().or_else(|| cfg.shell.clone());
+    exec_opts.timeout = cmd.timeout.map(|t| t.0);
+    exec_opts.label = ctx.output_label.clone();
+
+    let mut inv = Invocation { cmd, cfg, group: current_config, args: args.clone(), opts, exec_opts };
+
+    if !cmd.steps.is_empty() {
+        return run_steps(&mut inv, ctx, capture);
+    }
+
+    let rendered = render_cmd(cmd, current_config, args, &inv.opts)
+        .map_err(|e| CommandError::Render(format_error_chain(&e)))?;
+    let stdout = inv.run_script(ctx, &rendered, capture)?;
+    if ctx.dry_run && !ctx.print_only {
+        inv.print_dry_run_details(ctx);
+    }
+    Ok(stdout)
+}
+
+/**
+ Process options (env, `cwd` and shell) for a command run on behalf of `cmd` in the
+ context's active group, such as a `choices_from` command, rendered with the args
+ collected so far. Env values and `cwd` that need params not collected yet are left out.
+*/
+pub fn command_exec_options(
+    cmd: &CommandDef,
+    cfg: &Config,
+    ctx: &GlobalContext,
+    args: &HashMap<String, Value>,
+) -> Result<ExecOptions, CommandError> {
+    let opts = RenderOptions {
+        allow_prompts: !ctx.no_input,
+        secrets: backend_for(cfg, !ctx.no_input),
+        revealed: Default::default(),
+    };
+    let mut exec_opts = exec_options(cmd, active_group(cfg, ctx)?, args, &opts, true)?;
+    exec_opts.shell = cmd.shell.clone().or_else(|| cfg.shell.clone());
+    Ok(exec_opts)
+}
 
+/** Params used when no groups are configured (no params file) */
+static NO_GROUP: LazyLock<UserParams> = LazyLock::new(UserParams::default);
+
+/** Params of the active group, or empty params if there are no groups. */
+pub fn active_group<'a>(cfg: &'a Config, ctx: &GlobalContext) -> Result<&'a UserParams, CommandError> {
+    if cfg.params.is_empty() {
+        return Ok(&NO_GROUP);
+    }
     let sub_name = ctx.current_group.as_ref()
-        .ok_or_else(|| "No group selected".to_string())?;
+        .ok_or_else(|| CommandError::Config("No group selected".to_string()))?;
+
+    cfg.params.get(sub_name)
+        .ok_or_else(|| CommandError::Config(format!("Group '{}' not found", sub_name)))
+}
+
+/**
+ The command as it will run, for showing beforehand: the rendered `exec`, or each
+ rendered step (the commands a `parallel` step runs), with secrets redacted. Nothing is prompted for; templates that
+ cannot be rendered yet (e.g. using captured output) are shown as written.
+*/
+pub fn preview_command(
+    cmd: &CommandDef,
+    cfg: &Config,
+    ctx: &GlobalContext,
+    args: &HashMap<String, Value>,
+) -> Result<Vec<String>, CommandError> {
+    let group = active_group(cfg, ctx)?;
+    let opts = preview_options(cfg);
 
-    let current_config: &UserParams = cfg.params.get(sub_name)
-        .ok_or_else(|| format!("Group '{}' not found", sub_name))?;
+    let render = |t: &str| render_template(t, group, args, &opts).unwrap_or_else(|_| t.to_string());
+    let rendered: Vec<String> = if cmd.steps.is_empty() {
+        vec![render(&cmd.exec)]
+    } else {
+        cmd.steps
+            .iter()
+            .map(|s| {
+                if s.parallel.is_empty() {
+                    render(&s.exec)
+                } else {
+                    format!("(in parallel: {})", s.parallel.join(", "))
+                }
+            })
+            .collect()
+    };
 
-    let rendered = render_cmd(cmd, current_config, args)
-        .map_err(|e| format!("Failed to render: {}", format_error_chain(&e)))?;
+    let mut secrets = secret_values(cmd, cfg, ctx, group, args);
+    if let Ok(revealed) = opts.revealed.lock() {
+        secrets.extend(revealed.iter().cloned());
+    }
+    Ok(rendered.iter().map(|r| redact(r, &secrets)).collect())
+}
 
-    // Colored, minimal shell-like prefix: `$ command`
-    println!("{} {}", "$".blue().bold(), rendered.as_str().bright_black());
+/** Render a message template (e.g. a confirmation question) like `preview_command`. */
+pub fn preview_template(
+    template: &str,
+    cfg: &Config,
+    ctx: &GlobalContext,
+    args: &HashMap<String, Value>,
+) -> Result<String, CommandError> {
+    let group = active_group(cfg, ctx)?;
+    Ok(render_template(template, group, args, &preview_options(cfg)).unwrap_or_else(|_| template.to_string()))
+}
 
-    execute_shell_command(&rendered)
-        .map_err(|e| format!("Failed to execute: {}", e))?;
+fn preview_options(cfg: &Config) -> RenderOptions {
+    RenderOptions {
+        allow_prompts: false,
+        secrets: backend_for(cfg, false),
+        revealed: Default::default(),
+    }
+}
 
+/**
+ Store captured output as a template variable: the text without trailing newlines,
+ or the parsed JSON value. Dry runs store a `<name>` placeholder instead.
+*/
+pub fn store_capture(
+    vars: &mut HashMap<String, Value>,
+    capture: &Capture,
+    stdout: &str,
+    dry_run: bool,
+) -> Result<(), CommandError> {
+    let value = if dry_run {
+        Value::from(format!("<{}>", capture.name()))
+    } else if capture.json() {
+        serde_json::from_str(stdout).map_err(|e| {
+            CommandError::Execute(format!("output captured as '{}' is not valid JSON: {}", capture.name(), e))
+        })?
+    } else {
+        Value::from(stdout.trim_end_matches(['\n', '\r']))
+    };
+    vars.insert(capture.name().to_string(), value);
     Ok(())
 }
 
+/**
+ Run the steps of a command in order. A failing step stops the command unless it
+ has `continue_on_error` (an interrupted step always does); steps whose `when` condition is false are skipped. A
+ summary of all steps is printed at the end. A `parallel` step runs catalogue commands at the same time.
+*/
+fn run_steps(inv: &mut Invocation, ctx: &GlobalContext, capture_all: bool) -> Result<String, CommandError> {
+    let total = inv.cmd.steps.len();
+    let prefix = ctx.output_label.as_deref();
+    let mut outcomes: Vec<(String, StepOutcome)> = Vec::new();
+    let mut failure: Option<CommandError> = None;
+    let mut captured_all = String::new();
+
+    for (i, step) in inv.cmd.steps.iter().enumerate() {
+        let label = step.name.clone().unwrap_or_else(|| format!("step {}", i + 1));
+        if failure.is_some() {
+            outcomes.push((label, StepOutcome::NotRun));
+            continue;
+        }
+
+        let header = format!("▶ [{}/{}] {}", i + 1, total, label);
+        if !inv.condition_holds(step)? {
+            if !ctx.print_only {
+                outln!(prefix, "{} {}", header.bold(), "(skipped)".bright_black());
+            }
+            outcomes.push((label, StepOutcome::Skipped));
+            continue;
+        }
+        if !ctx.print_only {
+            outln!(prefix, "{}", header.bold());
+        }
+
+        let started = Instant::now();
+        let result = if step.parallel.is_empty() {
+            let rendered = match inv.render(&step.exec) {
+                Ok(rendered) => rendered,
+                // captured JSON is only a placeholder in dry runs, so paths into it cannot be rendered
+                Err(_) if ctx.dry_run && uses_captured_output(inv.cmd, step) => {
+                    if !ctx.print_only {
+                        outln!(prefix, "{} {}", "$".blue().bold(), "(not rendered: uses captured output)".yellow());
+                    }
+                    outcomes.push((label, StepOutcome::Skipped));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            inv.run_script(ctx, &rendered, capture_all || step.capture.is_some())
+                .and_then(|stdout| match &step.capture {
+                    Some(capture) => store_capture(&mut inv.args, capture, &stdout, ctx.dry_run).map(|_| stdout),
+                    None => Ok(stdout),
+                })
+        } else {
+            run_parallel_step(inv, ctx, step).map(|_| String::new())
+        };
+        let outcome = match result {
+            Ok(stdout) => {
+                captured_all.push_str(&stdout);
+                StepOutcome::Succeeded(started.elapsed())
+            }
+            Err(e) => {
+                if !matches!(e, CommandError::Failed(_)) {
+                    errln!(prefix, "{}", e);
+                }
+                let (code, duration) = (e.exit_code(), started.elapsed());
+                // Ctrl-C stops the command, even with `continue_on_error`
+                let continued = step.continue_on_error && code != exit_codes::INTERRUPTED;
+                if !continued {
+                    // reported above; keep only the exit code
+                    failure = Some(CommandError::Failed(code));
+                }
+                StepOutcome::Failed { code, duration, continued }
+            }
+        };
+        outcomes.push((label, outcome));
+    }
+
+    if ctx.dry_run {
+        if !ctx.print_only {
+            inv.print_dry_run_details(ctx);
+        }
+    } else {
+        print_step_summary(&outcomes, prefix);
+    }
+
+    failure.map_or(Ok(captured_all), Err)
+}
+
+/** Run the catalogue commands of a `parallel` step at the same time, with the command's args
+    (including captured output). They get no other input: parameters they need beyond these
+    must have defaults. Fails with the exit code of the first command that failed. */
+fn run_parallel_step(inv: &Invocation, ctx: &GlobalContext, step: &StepDef) -> Result<(), CommandError> {
+    let mut jobs: Vec<CommandJob> = Vec::new();
+    for name in &step.parallel {
+        let cmd = find_command(&inv.cfg.categories, name)
+            .ok_or_else(|| CommandError::Config(format!("Command '{}' not found in the catalogue", name)))?;
+        let args = inherit_parameters(cmd, &inv.args, false, &ParamSource::new(inv.cfg, ctx))?;
+        jobs.push(CommandJob { name: name.clone(), cmd, ctx: ctx.clone(), args });
+    }
+
+    let results = run_command_jobs("command", &jobs, inv.cfg, true, false);
+    match combined_exit_code(&results) {
+        exit_codes::SUCCESS => Ok(()),
+        code => Err(CommandError::Failed(code)),
+    }
+}
+
+/** True if the step's template references a variable captured by another step of the command. */
+fn uses_captured_output(cmd: &CommandDef, step: &StepDef) -> bool {
+    cmd.steps
+        .iter()
+        .filter_map(|s| s.capture.as_ref())
+        .any(|c| Regex::new(&format!(r"\b{}\b", regex::escape(c.name()))).is_ok_and(|re| re.is_match(&step.exec)))
+}
+
+fn print_step_summary(outcomes: &[(String, StepOutcome)], prefix: Option<&str>) {
+    outln!(prefix, "{}", "Summary:".bold());
+    for (label, outcome) in outcomes {
+        let line = match outcome {
+            StepOutcome::Succeeded(duration) => {
+                format!("  ✔ {} ({})", label, format_elapsed(*duration)).green()
+            }
+            StepOutcome::Failed { code, duration, continued } => format!(
+                "  ✘ {} (exit code {}, {}{})",
+                label,
+                code,
+                format_elapsed(*duration),
+                if *continued { ", continued" } else { "" }
+            )
+            .red(),
+            StepOutcome::Skipped => format!("  - {} (skipped)", label).bright_black(),
+            StepOutcome::NotRun => format!("  - {} (not run)", label).bright_black(),
+        };
+        outln!(prefix, "{}", line);
+    }
+}
+
+impl Invocation<'_> {
+    /** Render a template of this command with the group params and collected args. */
+    fn render(&self, template: &str) -> Result<String, CommandError> {
+        render_template(template, self.group, &self.args, &self.opts)
+            .map_err(|e| CommandError::Render(format_error_chain(&e)))
+    }
+
+    /** Evaluate a step's `when` condition (true when there is none). */
+    fn condition_holds(&self, step: &StepDef) -> Result<bool, CommandError> {
+        match &step.when {
+            Some(condition) => {
+                let rendered = self.render(&format!("{{% if {} %}}true{{% endif %}}", condition))?;
+                Ok(rendered == "true")
+            }
+            None => Ok(true),
+        }
+    }
+
+    /** Values that must not be shown, including secrets revealed while rendering so far. */
+    fn secrets(&self, ctx: &GlobalContext) -> Vec<String> {
+        let mut secrets = secret_values(self.cmd, self.cfg, ctx, self.group, &self.args);
+        if let Ok(revealed) = self.opts.revealed.lock() {
+            secrets.extend(revealed.iter().cloned());
+        }
+        secrets
+    }
+
+    /** Show a rendered script and, unless this is a dry run, execute it. With `capture`, the
+        script's stdout is returned instead of shown. */
+    fn run_script(&self, ctx: &GlobalContext, rendered: &str, capture: bool) -> Result<String, CommandError> {
+        let label = ctx.output_label.as_deref();
+        // Raw command only, suitable for piping
+        if ctx.print_only {
+            outln!(label, "{}", rendered);
+            return Ok(String::new());
+        }
+
+        // Colored, minimal shell-like prefix: `$ command` (secrets redacted)
+        let shown = redact(rendered, &self.secrets(ctx));
+        outln!(label, "{} {}", "$".blue().bold(), shown.as_str().bright_black());
+
+        if ctx.dry_run {
+            return Ok(String::new());
+        }
+        execute_with_retry(self.cmd, rendered, &self.exec_opts, capture)
+    }
+
+    /** Show the process options of a dry run. */
+    fn print_dry_run_details(&self, ctx: &GlobalContext) {
+        let label = ctx.output_label.as_deref();
+        let exec_opts = &self.exec_opts;
+        if let Some(shell) = &exec_opts.shell {
+            outln!(label, "  {} {}", "shell:".bright_black(), shell);
+        }
+        if let Some(cwd) = &exec_opts.cwd {
+            outln!(label, "  {} {}", "cwd:".bright_black(), cwd.display());
+        }
+        if let Some(timeout) = exec_opts.timeout {
+            outln!(label, "  {} {}", "timeout:".bright_black(), format_duration(timeout));
+        }
+        if let Some(retry) = &self.cmd.retry {
+            outln!(label, "  {} {} attempts", "retry:".bright_black(), retry.attempts);
+        }
+        let secrets = self.secrets(ctx);
+        let mut env: Vec<_> = exec_opts.env.iter().collect();
+        env.sort();
+        for (k, v) in env {
+            outln!(label, "  {} {}={}", "env:".bright_black(), k, redact(v, &secrets));
+        }
+        outln!(label, "{}", "(dry run: not executed)".yellow());
+    }
+}
+
+/** Run the rendered command, re-running it on failures matching the command's `retry` policy.
+    Returns the captured stdout of the successful run (empty unless `capture`). */
+fn execute_with_retry(
+    cmd: &CommandDef,
+    rendered: &str,
+    exec_opts: &ExecOptions,
+    capture: bool,
+) -> Result<String, CommandError> {
+    let attempts = cmd.retry.as_ref().map_or(1, |r| r.attempts.max(1));
+    let mut delay = cmd.retry.as_ref().map(|r| r.delay.0).unwrap_or_default();
+
+    let mut attempt = 1;
+    loop {
+        let (status, stdout) = if capture {
+            execute_capturing_stdout(rendered, exec_opts)
+        } else {
+            execute_shell_command(rendered, exec_opts).map(|status| (status, String::new()))
+        }
+        .map_err(CommandError::Execute)?;
+
+        let error = match status {
+            ExecStatus::Exited(0) => return Ok(stdout),
+            ExecStatus::Exited(code) => CommandError::Failed(code),
+            ExecStatus::TimedOut => CommandError::TimedOut(exec_opts.timeout.unwrap_or_default()),
+        };
+
+        let retry = match &cmd.retry {
+            Some(policy) if attempt < attempts && policy.retries(error.exit_code()) => policy,
+            _ => return Err(error),
+        };
+
+        let reason = match &error {
+            CommandError::TimedOut(timeout) => format!("timed out after {}", format_duration(*timeout)),
+            _ => format!("failed with exit code {}", error.exit_code()),
+        };
+        errln!(
+            exec_opts.label.as_deref(),
+            "{}",
+            format!("Attempt {}/{} {}, retrying in {}", attempt, attempts, reason, format_duration(delay)).yellow()
+        );
+        thread::sleep(delay);
+        delay = delay.mul_f64(retry.backoff.max(0.0));
+        attempt += 1;
+    }
+}
+
+/** Render the active group's `env`, the command's `env` values and `cwd` into options for the
+    spawned process. Command env values override group env values of the same name. With
+    `partial`, values that cannot be rendered yet (e.g. using params not collected so far)
+    are left out instead of failing. */
+fn exec_options(
+    cmd: &CommandDef,
+    group_params: &UserParams,
+    args: &HashMap<String, Value>,
+    opts: &RenderOptions,
+    partial: bool,
+) -> Result<ExecOptions, CommandError> {
+    let mut exec_opts = ExecOptions::default();
+
+    for (key, template) in group_params.env.iter().chain(cmd.env.iter()) {
+        match render_template(template, group_params, args, opts) {
+            Ok(value) => {
+                exec_opts.env.insert(key.clone(), value);
+            }
+            Err(e) if partial => {
+                debug!("Leaving out env '{}': {}", key, format_error_chain(&e));
+                exec_opts.env.remove(key);
+            }
+            Err(e) => return Err(CommandError::Render(format!("env '{}': {}", key, format_error_chain(&e)))),
+        }
+    }
+
+    if let Some(template) = &cmd.cwd {
+        let rendered = match render_template(template, group_params, args, opts) {
+            Ok(rendered) => rendered,
+            Err(e) if partial => {
+                debug!("Leaving out cwd: {}", format_error_chain(&e));
+                return Ok(exec_opts);
+            }
+            Err(e) => return Err(CommandError::Render(format!("cwd: {}", format_error_chain(&e)))),
+        };
+        let cwd = PathBuf::from(expand_home(rendered.trim()));
+        if !cwd.is_dir() {
+            return Err(CommandError::Execute(format!(
+                "Working directory '{}' does not exist",
+                cwd.display()
+            )));
+        }
+        exec_opts.cwd = Some(cwd);
+    }
+
+    Ok(exec_opts)
+}
+
+/** Values that must not be shown: params marked `secret` by the command or by the commands
+    it runs on behalf of (see `GlobalContext::secret_params`), and secret keys of the active group. */
+pub fn secret_values(
+    cmd: &CommandDef,
+    cfg: &Config,
+    ctx: &GlobalContext,
+    group_params: &UserParams,
+    args: &HashMap<String, Value>,
+) -> Vec<String> {
+    let mut secrets: Vec<String> = Vec::new();
+
+    let own = cmd.params.iter().filter(|p| p.secret).map(|p| &p.name);
+    for name in own.chain(&ctx.secret_params) {
+        match args.get(name) {
+            Some(Value::Array(items)) => secrets.extend(items.iter().map(value_to_string)),
+            Some(value) => secrets.push(value_to_string(value)),
+            None => {}
+        }
+    }
+
+    for key in &cfg.secret_params {
+        if let Some(value) = group_params.fields.get(key) {
+            match value {
+                serde_yaml::Value::String(s) => secrets.push(s.clone()),
+                other => {
+                    if let Ok(s) = serde_yaml::to_string(other) {
+                        secrets.push(s.trim().to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    secrets
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
 fn format_error_chain(err: &dyn std::error::Error) -> String {
     let mut parts: Vec<String> = Vec::new();
     parts.push(err.to_string());
@@ -7,19 +7,30 @@ pub fn normalize_name(s: &str) -> String {
     s.to_lowercase().replace(" ", "")
 }
 
+/** Normalized dotted name of a command, e.g. `demo.sub.basic`. Commands listed outside of
+    a category (with an empty category name) are named by their own name only. */
+pub fn command_key(category: &str, subcategory: Option<&str>, name: &str) -> String {
+    let parts: Vec<&str> = [Some(category), subcategory, Some(name)]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.trim().is_empty())
+        .collect();
+    normalize_name(&parts.join("."))
+}
+
 /** Find a command by name across categories and subcategories. */
 pub fn find_command<'a>(categories: &'a [Category], name: &str) -> Option<&'a CommandDef> {
     let search_cmd_name = normalize_name(name);
     for cat in categories {
         for cmd in &cat.commands {
-            let cmd_name = normalize_name(&format!("{}.{}", &cat.category, &cmd.name));
+            let cmd_name = command_key(&cat.category, None, &cmd.name);
             if cmd_name == search_cmd_name {
                 return Some(cmd);
             }
         }
         for sub in &cat.subcategories {
             for cmd in &sub.commands {
-                let cmd_name = normalize_name(&format!("{}.{}.{}", &cat.category, &sub.name, &cmd.name));
+                let cmd_name = command_key(&cat.category, Some(&sub.name), &cmd.name);
                 if cmd_name == search_cmd_name {
                     return Some(cmd);
                 }
@@ -28,3 +39,18 @@ pub fn find_command<'a>(categories: &'a [Category], name: &str) -> Option<&'a Co
     }
     None
 }
+
+/** Dotted name of a command from the catalogue (e.g. `demo.basic`), as accepted by `find_command`. */
+pub fn command_path(categories: &[Category], target: &CommandDef) -> Option<String> {
+    for cat in categories {
+        if cat.commands.iter().any(|cmd| std::ptr::eq(cmd, target)) {
+            return Some(command_key(&cat.category, None, &target.name));
+        }
+        for sub in &cat.subcategories {
+            if sub.commands.iter().any(|cmd| std::ptr::eq(cmd, target)) {
+                return Some(command_key(&cat.category, Some(&sub.name), &target.name));
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,157 @@
+/** Typed parameter validation and conversion to template values. */
+use crate::config::{ParamDef, ParamType};
+use crate::utils::string::expand_home;
+use regex::Regex;
+use serde_json::Value;
+
+/**
+ Validate a raw value against the parameter's type and constraints and convert
+ it to the value exposed to templates (numbers and booleans are native types).
+*/
+pub fn parse_typed(param: &ParamDef, raw: &str) -> Result<Value, String> {
+    let raw = raw.trim();
+
+    // optional parameters may be left empty
+    if raw.is_empty() && param.optional {
+        return Ok(Value::from(""));
+    }
+
+    if let Some(pattern) = &param.pattern {
+        let re = Regex::new(&format!("^(?:{})$", pattern))
+            .map_err(|e| format!("Invalid pattern for parameter '{}': {}", param.name, e))?;
+        if !re.is_match(raw) {
+            return Err(format!(
+                "Invalid value '{}' for parameter '{}': must match pattern '{}'",
+                raw, param.name, pattern
+            ));
+        }
+    }
+
+    match param.kind {
+        ParamType::String => Ok(Value::from(raw)),
+        ParamType::Int => {
+            let n: i64 = raw
+                .parse()
+                .map_err(|_| invalid(param, raw, "expected an integer"))?;
+            check_range(param, raw, n as f64)?;
+            Ok(Value::from(n))
+        }
+        ParamType::Float => {
+            let n: f64 = raw
+                .parse()
+                .map_err(|_| invalid(param, raw, "expected a number"))?;
+            check_range(param, raw, n)?;
+            Ok(Value::from(n))
+        }
+        ParamType::Bool => parse_bool(raw)
+            .map(Value::from)
+            .ok_or_else(|| invalid(param, raw, "expected true/false, yes/no or 1/0")),
+        ParamType::Path => {
+            if raw.is_empty() {
+                return Err(invalid(param, raw, "expected a path"));
+            }
+            Ok(Value::from(expand_home(raw)))
+        }
+        ParamType::Url => {
+            let re = Regex::new(r"^[A-Za-z][A-Za-z0-9+.-]*://[^\s/?#]+[^\s]*$").unwrap();
+            if !re.is_match(raw) {
+                return Err(invalid(param, raw, "expected a URL such as https://example.com"));
+            }
+            Ok(Value::from(raw))
+        }
+        ParamType::Enum => {
+            if param.choices.is_empty() && param.choices_from.is_none() {
+                return Err(format!(
+                    "Parameter '{}' is of type 'enum' but defines no choices",
+                    param.name
+                ));
+            }
+            Ok(Value::from(raw))
+        }
+    }
+}
+
+/** Parse common boolean spellings. */
+pub fn parse_bool(raw: &str) -> Option<bool> {
+    match raw.to_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" | "on" => Some(true),
+        "false" | "no" | "n" | "0" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn check_range(param: &ParamDef, raw: &str, n: f64) -> Result<(), String> {
+    if let Some(min) = param.min && n < min {
+        return Err(invalid(param, raw, &format!("must be at least {}", min)));
+    }
+    if let Some(max) = param.max && n > max {
+        return Err(invalid(param, raw, &format!("must be at most {}", max)));
+    }
+    Ok(())
+}
+
+fn invalid(param: &ParamDef, raw: &str, reason: &str) -> String {
+    format!("Invalid value '{}' for parameter '{}': {}", raw, param.name, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(kind: ParamType) -> ParamDef {
+        ParamDef { name: "p".to_string(), kind, ..Default::default() }
+    }
+
+    #[test]
+    fn converts_ints() {
+        assert_eq!(parse_typed(&param(ParamType::Int), " 42 "), Ok(Value::from(42)));
+        assert_eq!(parse_typed(&param(ParamType::Int), "-3"), Ok(Value::from(-3)));
+        assert!(parse_typed(&param(ParamType::Int), "4.2").is_err());
+        assert!(parse_typed(&param(ParamType::Int), "abc").is_err());
+    }
+
+    #[test]
+    fn converts_floats() {
+        assert_eq!(parse_typed(&param(ParamType::Float), "1.5"), Ok(Value::from(1.5)));
+        assert_eq!(parse_typed(&param(ParamType::Float), "2"), Ok(Value::from(2.0)));
+        assert!(parse_typed(&param(ParamType::Float), "1,5").is_err());
+    }
+
+    #[test]
+    fn converts_bools() {
+        for raw in ["true", "Yes", "y", "1", "on"] {
+            assert_eq!(parse_typed(&param(ParamType::Bool), raw), Ok(Value::from(true)), "{}", raw);
+        }
+        for raw in ["false", "NO", "n", "0", "off"] {
+            assert_eq!(parse_typed(&param(ParamType::Bool), raw), Ok(Value::from(false)), "{}", raw);
+        }
+        assert!(parse_typed(&param(ParamType::Bool), "maybe").is_err());
+    }
+
+    #[test]
+    fn checks_min_and_max() {
+        let replicas = ParamDef { min: Some(1.0), max: Some(10.0), ..param(ParamType::Int) };
+        assert_eq!(parse_typed(&replicas, "1"), Ok(Value::from(1)));
+        assert_eq!(parse_typed(&replicas, "10"), Ok(Value::from(10)));
+        assert!(parse_typed(&replicas, "0").unwrap_err().contains("must be at least 1"));
+        assert!(parse_typed(&replicas, "11").unwrap_err().contains("must be at most 10"));
+
+        let ratio = ParamDef { min: Some(0.0), max: Some(1.0), ..param(ParamType::Float) };
+        assert!(parse_typed(&ratio, "0.5").is_ok());
+        assert!(parse_typed(&ratio, "1.01").is_err());
+    }
+
+    #[test]
+    fn checks_patterns() {
+        let name = ParamDef { pattern: Some("[a-z]+".to_string()), ..param(ParamType::String) };
+        assert!(parse_typed(&name, "abc").is_ok());
+        assert!(parse_typed(&name, "abc1").is_err());
+    }
+
+    #[test]
+    fn allows_empty_optional_values() {
+        let count = ParamDef { optional: true, ..param(ParamType::Int) };
+        assert_eq!(parse_typed(&count, ""), Ok(Value::from("")));
+        assert!(parse_typed(&param(ParamType::Int), "").is_err());
+    }
+}
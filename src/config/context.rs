@@ -13,8 +13,8 @@ pub fn create_context(cfg: &Config) -> GlobalContext {
         ctx.current_group = Some(def.clone());
     }
 
-    // Load last used values (if present)
-    if let Some(val) = load_last_value("group") { ctx.current_group = Some(val); }
+    // Load last used values (if present and still configured)
+    if let Some(val) = load_last_value("group").filter(|g| cfg.params.contains_key(g)) { ctx.current_group = Some(val); }
 
     ctx
 }
@@ -26,7 +26,6 @@ pub fn set_last_used(key: &str, value: &str) {
 }
 
 /** Convenience to get the config data dir used by this app. */
-#[allow(dead_code)]
 pub fn config_data_dir() -> PathBuf {
     let mut dir = config_dir().unwrap_or_default();
     dir.push("simple-cli");
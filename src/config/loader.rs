@@ -1,9 +1,13 @@
-use std::{collections::HashMap, fs, path::{Path, PathBuf}, env};
+use std::{collections::{BTreeMap, HashMap, HashSet}, fs, path::{Path, PathBuf}, env};
 use serde::de::DeserializeOwned;
 use serde_yaml::Value;
-use super::models::{ConfigFile, Config};
+use super::models::{Category, CommandDef, ConfigFile, Config, ParamType, UserParams};
+use crate::cli::subcommand_names;
+use crate::commands::{command_key, normalize_name};
+use crate::utils::string::{expand_env_vars, expand_home};
 use log::{debug, info, warn, error};
 use thiserror::Error;
+use regex::Regex;
 
 /// Error type for configuration loading
 #[derive(Debug, Error)]
@@ -17,56 +21,149 @@ pub enum ConfigLoadError {
     #[error("Requested section '{0}' is missing in {1:?}")]
     MissingSection(String, PathBuf),
 
-    #[error("Config file key missing: {0}")]
-    MissingConfigKey(String),
+    #[error("Invalid command '{0}' in {1:?}: {2}")]
+    InvalidCommand(String, PathBuf, String),
 
-    #[error("Could not find all config files in any candidate directory:\n{0}")]
+    #[error("Invalid include in {0:?}: {1}")]
+    InvalidInclude(PathBuf, String),
+
+    #[error("Command '{0}' is defined twice: in {1:?} and in {2:?}")]
+    DuplicateCommand(String, PathBuf, PathBuf),
+
+    #[error("Could not find all config files:\n{0}")]
     MissingConfigFiles(String),
+
+    #[error("{0:?} not found")]
+    NotFound(PathBuf),
+}
+
+/// Config locations given on the command line, overriding the search for config files
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    /// Read the config files from this directory only
+    pub dir: Option<PathBuf>,
+    /// Use this commands file instead of any found
+    pub commands_file: Option<PathBuf>,
+    /// Use this params file instead of any found
+    pub params_file: Option<PathBuf>,
+}
+
+impl ConfigOverrides {
+    /// The file given for a key of `Config.files`, if any.
+    fn file_for(&self, key: &str) -> Option<&PathBuf> {
+        match key {
+            "commandsFile" => self.commands_file.as_ref(),
+            "paramsFile" => self.params_file.as_ref(),
+            _ => None,
+        }
+    }
 }
 
+/// Directory next to the commands file whose YAML files are added to the catalogue
+const COMMANDS_DIR: &str = "scli.commands.d";
+
 /// Top-level config loader
-pub fn load_config() -> Result<Config, ConfigLoadError> {
+pub fn load_config(overrides: &ConfigOverrides) -> Result<Config, ConfigLoadError> {
     let mut config = Config::default();
 
     debug!("Starting config load");
 
-    // Locate directory containing all required ConfigFiles
-    config.files = get_config_dir(&config.files)?;
-
-    // Get file paths
-    let params_file = config.files
-        .get("paramsFile")
-        .ok_or_else(|| ConfigLoadError::MissingConfigKey("paramsFile".to_string()))?;
-    let commands_file = config.files
-        .get("commandsFile")
-        .ok_or_else(|| ConfigLoadError::MissingConfigKey("commandsFile".to_string()))?;
+    // Locate the layers of config files, lowest precedence first
+    let layers = get_config_layers(&config.files, overrides)?;
+    // without a params file, commands run without groups
+    config.files.retain(|key, _| layers.iter().any(|layer| layer.contains_key(key)));
 
-    // Read the params file
-    debug!("Reading params file at {:?}", params_file.path);
-    let params_yaml = read_yaml_file(&params_file.path)?;
+    for layer in &layers {
+        // Read the params file
+        if let Some(params_file) = layer.get("paramsFile") {
+            debug!("Reading params file at {:?}", params_file.path);
+            let params_yaml = read_yaml_file(&params_file.path)?;
+            merge_params(&mut config, &params_yaml, &params_file.path)?;
+            config.sources.files.push(params_file.path.clone());
+        }
 
-    debug!("Loading defaults from params file");
-    config.default_group = parse_section_from_value(&params_yaml, Some("default_group"), &params_file.path)?;
+        // Read commands file, the files it includes and scli.commands.d/
+        if let Some(commands_file) = layer.get("commandsFile") {
+            debug!("Loading commands/categories from commands file at {:?}", commands_file.path);
+            let sources = load_commands(&commands_file.path)?;
+            config.sources.files.extend(unique_files(&sources));
+            merge_categories(&mut config.categories, sources, &mut config.sources.commands);
+        }
 
-    debug!("Loading params/groups from params file");
-    config.params = parse_section_from_value(&params_yaml, Some("groups"), &params_file.path)?;
+        // The files of the highest layer are shown as the config's files
+        for (key, file) in layer {
+            config.files.insert(key.clone(), file.clone());
+        }
+    }
 
-    // Read commands file
-    debug!("Reading commands file at {:?}", commands_file.path);
-    let commands_yaml = read_yaml_file(&commands_file.path)?;
+    // A params file without groups only holds settings (e.g. `shell`); groups need a default
+    if let Some(params_file) = config.files.get("paramsFile")
+        && !config.params.is_empty()
+        && config.default_group.is_none()
+    {
+        return Err(ConfigLoadError::MissingSection("default_group".to_string(), params_file.path.clone()));
+    }
 
-    debug!("Loading commands/categories from commands file");
-    config.categories = parse_section_from_value(&commands_yaml, None, &commands_file.path)?;
+    debug!("Expanding environment variables in params and defaults");
+    expand_config_env_vars(&mut config);
 
     info!("Configuration loaded successfully");
     Ok(config)
 }
 
-/// Given a map of expected files (key -> ConfigFile with `filename` set),
-/// find a directory that contains *all* those files co-located.
-///
-/// Candidate search order: $SIMPLE_CLI_DIR, $HOME/SimpleCli, current working dir.
-pub fn get_config_dir(files: &HashMap<String, ConfigFile>) -> Result<HashMap<String, ConfigFile>, ConfigLoadError> {
+/// Merge the sections of a params file into the config. Groups are merged param by param
+/// (and `env` variable by variable), with values from this file taking precedence; a group
+/// is protected if any file protects it. Secret keys are added; other settings are replaced.
+fn merge_params(config: &mut Config, params_yaml: &Value, path: &Path) -> Result<(), ConfigLoadError> {
+    if params_yaml.get("default_group").is_some() {
+        debug!("Loading defaults from params file");
+        config.default_group = parse_section_from_value(params_yaml, Some("default_group"), path)?;
+    }
+
+    if params_yaml.get("groups").is_some() {
+        debug!("Loading params/groups from params file");
+        let groups: HashMap<String, UserParams> = parse_section_from_value(params_yaml, Some("groups"), path)?;
+        for (name, group) in groups {
+            for key in group.fields.keys() {
+                config.sources.params.insert(format!("{}.{}", name, key), path.to_path_buf());
+            }
+            for key in group.env.keys() {
+                config.sources.params.insert(format!("{}.env.{}", name, key), path.to_path_buf());
+            }
+            let merged = config.params.entry(name).or_default();
+            merged.fields.extend(group.fields);
+            merged.env.extend(group.env);
+            merged.protected |= group.protected;
+        }
+    }
+
+    if params_yaml.get("secret_params").is_some() {
+        debug!("Loading secret param keys from params file");
+        let keys: Vec<String> = parse_section_from_value(params_yaml, Some("secret_params"), path)?;
+        for key in keys {
+            if !config.secret_params.contains(&key) {
+                config.secret_params.push(key);
+            }
+        }
+    }
+
+    if params_yaml.get("secret_backend").is_some() {
+        debug!("Loading secret backend from params file");
+        config.secret_backend = parse_section_from_value(params_yaml, Some("secret_backend"), path)?;
+    }
+
+    if params_yaml.get("shell").is_some() {
+        debug!("Loading default shell from params file");
+        config.shell = parse_section_from_value(params_yaml, Some("shell"), path)?;
+    }
+    Ok(())
+}
+
+/// Directories whose config files are layered, lowest precedence first: $SIMPLE_CLI_DIR,
+/// $HOME/SimpleCli, the ancestors of the current dir up to the root of its git repository
+/// or $HOME, whichever is nearer (outermost first), and the current dir itself. A directory
+/// is listed once, at its highest precedence.
+fn candidate_dirs() -> Vec<PathBuf> {
     let mut candidates: Vec<PathBuf> = Vec::new();
 
     // Envvar
@@ -89,63 +186,384 @@ pub fn get_config_dir(files: &HashMap<String, ConfigFile>) -> Result<HashMap<Str
         }
     }
 
-    // CWD
+    // Ancestors up to the git root, and CWD
     match env::current_dir() {
         Ok(cwd) => {
+            if let Some(top) = search_root(&cwd) {
+                let mut ancestors: Vec<PathBuf> = cwd
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|dir| dir.starts_with(&top))
+                    .map(Path::to_path_buf)
+                    .collect();
+                ancestors.reverse();
+                debug!("Using ancestor candidates up to {:?}: {:?}", top, ancestors);
+                candidates.extend(ancestors);
+            }
             debug!("Using current dir candidate: {:?}", cwd);
             candidates.push(cwd);
         }
         Err(e) => warn!("Failed to get current working directory: {}", e),
     }
 
+    // keep the last (highest precedence) occurrence of each directory
+    let canonical: Vec<PathBuf> = candidates.iter().map(|p| p.canonicalize().unwrap_or_else(|_| p.clone())).collect();
+    candidates
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !canonical[i + 1..].contains(&canonical[*i]))
+        .map(|(_, p)| p)
+        .collect()
+}
+
+/// Where the search upwards from `dir` stops: the nearest ancestor that is the root of a git
+/// repository or the home directory. Outside of both, there is nothing to search.
+fn search_root(dir: &Path) -> Option<PathBuf> {
+    let home = dirs::home_dir();
+    dir.ancestors()
+        .find(|d| d.join(".git").exists() || home.as_deref() == Some(*d))
+        .map(Path::to_path_buf)
+}
+
+/// Given a map of expected files (key -> ConfigFile with `filename` set), find the layers of
+/// config: for each candidate directory (lowest precedence first) that contains any of the
+/// files, the files it contains. Every file that is not optional must be found in at least
+/// one directory.
+///
+/// With `overrides.dir`, that directory is the only candidate; a file given in `overrides`
+/// is used instead of the files of that kind found in the candidates.
+pub fn get_config_layers(
+    files: &HashMap<String, ConfigFile>,
+    overrides: &ConfigOverrides,
+) -> Result<Vec<HashMap<String, ConfigFile>>, ConfigLoadError> {
+    let candidates = match &overrides.dir {
+        Some(dir) if !dir.is_dir() => return Err(ConfigLoadError::NotFound(dir.clone())),
+        Some(dir) => vec![dir.clone()],
+        None => candidate_dirs(),
+    };
     if candidates.is_empty() {
         return Err(ConfigLoadError::MissingConfigFiles(
             "No candidate directories available".to_string()
         ));
     }
 
-    // For diagnostics: which files are missing per directory
-    let mut diagnostics: Vec<(PathBuf, Vec<String>)> = Vec::new();
-
-    // Try each candidate path
+    let mut layers: Vec<HashMap<String, ConfigFile>> = Vec::new();
     for dir in &candidates {
-        let mut cloned_map = files.clone();
-        let mut missing: Vec<String> = Vec::new();
-
         debug!("Checking candidate dir {:?}", dir);
-
-        for (_key, cfg_file) in cloned_map.iter_mut() {
+        let mut layer: HashMap<String, ConfigFile> = HashMap::new();
+        for (key, cfg_file) in files {
+            if overrides.file_for(key).is_some() {
+                continue;
+            }
             let path = dir.join(&cfg_file.filename);
-
             if path.exists() {
                 debug!("  {:?}: found", path);
-                cfg_file.path = path;
+                layer.insert(key.clone(), ConfigFile { path, ..cfg_file.clone() });
             } else {
                 debug!("  {:?}: missing", path);
-                missing.push(cfg_file.filename.clone());
             }
         }
+        if !layer.is_empty() {
+            layers.push(layer);
+        }
+    }
 
-        if missing.is_empty() {
-            debug!("All required files found in {:?}", dir);
-            return Ok(cloned_map);
-        } else {
-            debug!("Candidate {:?} is missing files: {:?}", dir, missing);
-            diagnostics.push((dir.clone(), missing));
+    // Files given explicitly take precedence over everything else
+    let mut given: HashMap<String, ConfigFile> = HashMap::new();
+    for (key, cfg_file) in files {
+        if let Some(path) = overrides.file_for(key) {
+            if !path.is_file() {
+                return Err(ConfigLoadError::NotFound(path.clone()));
+            }
+            given.insert(key.clone(), ConfigFile { path: path.clone(), ..cfg_file.clone() });
         }
     }
+    if !given.is_empty() {
+        layers.push(given);
+    }
+
+    let mut missing: Vec<String> = files
+        .iter()
+        .filter(|(key, f)| !f.optional && !layers.iter().any(|layer| layer.contains_key(*key)))
+        .map(|(_, f)| f.filename.clone())
+        .collect();
+    if missing.is_empty() {
+        return Ok(layers);
+    }
+    missing.sort();
 
-    // Message describing missing files per candidate
-    let mut msg = String::from("Missing files per candidate directory:\n");
-    for (dir, missing) in &diagnostics {
-        msg.push_str(&format!("- {:?} missing: {:?}\n", dir, missing));
+    // Message describing the missing files and where they were looked for
+    let mut msg = format!("Missing files: {:?}\nSearched directories:\n", missing);
+    for dir in &candidates {
+        msg.push_str(&format!("- {:?}\n", dir));
     }
-    msg.push_str("Searched candidates: SIMPLE_CLI_DIR, $HOME/SimpleCli, current working directory.");
+    msg.push_str("Searched candidates: SIMPLE_CLI_DIR, $HOME/SimpleCli, parent directories up to the git root or $HOME, current working directory.");
 
     error!("{}", msg);
     Err(ConfigLoadError::MissingConfigFiles(msg))
 }
 
+/// Read the categories of the commands file, the files it includes and the files in
+/// `scli.commands.d/` next to it (in name order), each with the file it came from.
+/// A command defined in more than one of these files is an error.
+fn load_commands(path: &Path) -> Result<Vec<(PathBuf, Category)>, ConfigLoadError> {
+    let mut sources: Vec<(PathBuf, Category)> = Vec::new();
+    let mut loaded: HashSet<PathBuf> = HashSet::new();
+    read_commands_file(path, &mut sources, &mut loaded)?;
+
+    let commands_dir = path.parent().unwrap_or(Path::new(".")).join(COMMANDS_DIR);
+    if commands_dir.is_dir() {
+        debug!("Reading commands directory {:?}", commands_dir);
+        let mut files = glob_files(&commands_dir.join("*.yaml"))?;
+        files.extend(glob_files(&commands_dir.join("*.yml"))?);
+        files.sort();
+        for file in files {
+            read_commands_file(&file, &mut sources, &mut loaded)?;
+        }
+    }
+
+    let mut defined_in: HashMap<String, &PathBuf> = HashMap::new();
+    for (file, category) in &sources {
+        for command_path in command_paths(category) {
+            if let Some(first) = defined_in.insert(command_path.clone(), file) {
+                return Err(ConfigLoadError::DuplicateCommand(command_path, first.clone(), file.clone()));
+            }
+        }
+    }
+    Ok(sources)
+}
+
+/// Normalized paths of the commands of a category, as accepted by `find_command`.
+fn command_paths(category: &Category) -> Vec<String> {
+    let name = &category.category;
+    let sub_commands = category
+        .subcategories
+        .iter()
+        .flat_map(|sub| sub.commands.iter().map(move |cmd| command_key(name, Some(&sub.name), &cmd.name)));
+    category
+        .commands
+        .iter()
+        .map(|cmd| command_key(name, None, &cmd.name))
+        .chain(sub_commands)
+        .collect()
+}
+
+/// The files categories came from, in order, each once.
+fn unique_files(sources: &[(PathBuf, Category)]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for (file, _) in sources {
+        if !files.contains(file) {
+            files.push(file.clone());
+        }
+    }
+    files
+}
+
+/// Read the categories of a commands file, in order, with the categories of the files
+/// its `- include: <glob or list of globs>` entries refer to (relative to the file) in
+/// place of those entries. Files already read are skipped.
+fn read_commands_file(
+    path: &Path,
+    sources: &mut Vec<(PathBuf, Category)>,
+    loaded: &mut HashSet<PathBuf>,
+) -> Result<(), ConfigLoadError> {
+    if !loaded.insert(path.canonicalize()?) {
+        debug!("Skipping {:?}: already loaded", path);
+        return Ok(());
+    }
+
+    let items = match read_yaml_file(path)? {
+        Value::Sequence(items) => items,
+        Value::Null => Vec::new(),
+        other => vec![other],
+    };
+    let base = path.parent().unwrap_or(Path::new("."));
+    for item in items {
+        let Some(include) = item.get("include") else {
+            // commands may also be listed outside of any category
+            let category: Category = if item.get("category").is_none() && item.get("name").is_some() {
+                Category { commands: vec![serde_yaml::from_value(item)?], ..Default::default() }
+            } else {
+                serde_yaml::from_value(item)?
+            };
+            validate_commands(std::slice::from_ref(&category), path)?;
+            sources.push((path.to_path_buf(), category));
+            continue;
+        };
+
+        for pattern in include_patterns(include, path)? {
+            let pattern = base.join(expand_home(&pattern));
+            let files = glob_files(&pattern)?;
+            let is_glob = pattern.to_string_lossy().contains(['*', '?', '[']);
+            if files.is_empty() && !is_glob {
+                return Err(ConfigLoadError::InvalidInclude(path.to_path_buf(), format!("{:?} not found", pattern)));
+            }
+            for file in files {
+                debug!("Including {:?}", file);
+                read_commands_file(&file, sources, loaded)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Patterns of an `include` entry: a single string or a list of strings.
+fn include_patterns(value: &Value, path: &Path) -> Result<Vec<String>, ConfigLoadError> {
+    let invalid = || ConfigLoadError::InvalidInclude(path.to_path_buf(), "expected a path or a list of paths".to_string());
+    match value {
+        Value::String(pattern) => Ok(vec![pattern.clone()]),
+        Value::Sequence(items) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string).ok_or_else(invalid))
+            .collect(),
+        _ => Err(invalid()),
+    }
+}
+
+/// Files matching a glob pattern, in name order.
+fn glob_files(pattern: &Path) -> Result<Vec<PathBuf>, ConfigLoadError> {
+    let pattern = pattern.to_string_lossy();
+    let paths = glob::glob(&pattern)
+        .map_err(|e| ConfigLoadError::InvalidInclude(PathBuf::from(pattern.as_ref()), e.to_string()))?;
+    Ok(paths.filter_map(Result::ok).filter(|p| p.is_file()).collect())
+}
+
+/// Merge categories into the tree: categories (and their subcategories) with the same name
+/// are combined, keeping the position of the first. A command with the same name as an
+/// existing one replaces it. `defined_in` records the file each command came from.
+fn merge_categories(
+    categories: &mut Vec<Category>,
+    sources: Vec<(PathBuf, Category)>,
+    defined_in: &mut BTreeMap<String, PathBuf>,
+) {
+    for (path, category) in sources {
+        for command_path in command_paths(&category) {
+            defined_in.insert(command_path, path.clone());
+        }
+
+        let existing = categories
+            .iter_mut()
+            .find(|c| normalize_name(&c.category) == normalize_name(&category.category));
+        let Some(existing) = existing else {
+            categories.push(category);
+            continue;
+        };
+        debug!("Merging category '{}' from {:?}", category.category, path);
+        if existing.description.is_empty() {
+            existing.description = category.description;
+        }
+        merge_commands(&mut existing.commands, category.commands);
+        for sub in category.subcategories {
+            match existing.subcategories.iter_mut().find(|s| normalize_name(&s.name) == normalize_name(&sub.name)) {
+                Some(existing_sub) => {
+                    if existing_sub.description.is_empty() {
+                        existing_sub.description = sub.description;
+                    }
+                    merge_commands(&mut existing_sub.commands, sub.commands);
+                }
+                None => existing.subcategories.push(sub),
+            }
+        }
+    }
+}
+
+/// Add commands to a list, replacing commands with the same name.
+fn merge_commands(commands: &mut Vec<CommandDef>, added: Vec<CommandDef>) {
+    for cmd in added {
+        match commands.iter_mut().find(|c| normalize_name(&c.name) == normalize_name(&cmd.name)) {
+            Some(existing) => *existing = cmd,
+            None => commands.push(cmd),
+        }
+    }
+}
+
+/// Check that every command defines exactly one of `exec` and `steps`, and every step
+/// exactly one of `exec` and `parallel`.
+fn validate_commands(categories: &[Category], path: &Path) -> Result<(), ConfigLoadError> {
+    let reserved = subcommand_names();
+    for cat in categories {
+        let sub_commands = cat.subcategories.iter().flat_map(|s| s.commands.iter().map(|c| (c, false)));
+        for (cmd, top_level) in cat.commands.iter().map(|c| (c, true)).chain(sub_commands) {
+            let invalid = |problem: String| {
+                ConfigLoadError::InvalidCommand(cmd.name.clone(), path.to_path_buf(), problem)
+            };
+            // `scli <name>` would run the subcommand instead
+            let key = command_key(&cat.category, None, &cmd.name);
+            if top_level && reserved.contains(&key) {
+                return Err(invalid(format!(
+                    "'{}' is a built-in subcommand; put the command in a category or rename it",
+                    key
+                )));
+            }
+            match (cmd.exec.trim().is_empty(), cmd.steps.is_empty()) {
+                (true, true) => return Err(invalid("either `exec` or `steps` is required".to_string())),
+                (false, false) => return Err(invalid("`exec` and `steps` cannot be combined".to_string())),
+                _ => {}
+            }
+
+            for (i, step) in cmd.steps.iter().enumerate() {
+                let problem = match (step.exec.trim().is_empty(), step.parallel.is_empty()) {
+                    (true, true) => "either `exec` or `parallel` is required",
+                    (false, false) => "`exec` and `parallel` cannot be combined",
+                    (true, false) if step.capture.is_some() => "`capture` cannot be used with `parallel`",
+                    _ => continue,
+                };
+                return Err(invalid(format!("step {}: {}", i + 1, problem)));
+            }
+
+            // checked here, as a value could never satisfy them
+            for param in &cmd.params {
+                if let Some(pattern) = &param.pattern
+                    && let Err(e) = Regex::new(&format!("^(?:{})$", pattern))
+                {
+                    return Err(invalid(format!("param '{}': invalid pattern: {}", param.name, e)));
+                }
+                if param.kind == ParamType::Enum && param.choices.is_empty() && param.choices_from.is_none() {
+                    return Err(invalid(format!(
+                        "param '{}': type 'enum' requires `choices` or `choices_from`",
+                        param.name
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Expand `${VAR}` / `${VAR:-fallback}` in group param values, group env and command param defaults.
+fn expand_config_env_vars(config: &mut Config) {
+    for group in config.params.values_mut() {
+        for value in group.fields.values_mut() {
+            expand_yaml_env_vars(value);
+        }
+        for value in group.env.values_mut() {
+            *value = expand_env_vars(value);
+        }
+    }
+
+    for cat in config.categories.iter_mut() {
+        let sub_commands = cat.subcategories.iter_mut().flat_map(|s| s.commands.iter_mut());
+        for cmd in cat.commands.iter_mut().chain(sub_commands) {
+            for param in cmd.params.iter_mut() {
+                if let Some(default) = param.default.as_mut() {
+                    *default = expand_env_vars(default);
+                }
+            }
+        }
+    }
+}
+
+/// Expand environment variables in every string inside a YAML value.
+fn expand_yaml_env_vars(value: &mut Value) {
+    match value {
+        Value::String(s) => *s = expand_env_vars(s),
+        Value::Sequence(items) => items.iter_mut().for_each(expand_yaml_env_vars),
+        Value::Mapping(map) => map.iter_mut().for_each(|(_, v)| expand_yaml_env_vars(v)),
+        Value::Tagged(tagged) => expand_yaml_env_vars(&mut tagged.value),
+        _ => {}
+    }
+}
+
 /// Read a YAML file return a serde_yaml::Value
 fn read_yaml_file(path: &Path) -> Result<Value, ConfigLoadError> {
     debug!("Reading YAML file {:?}", path);
@@ -178,4 +596,81 @@ fn parse_section_from_value<T: DeserializeOwned>(
             Ok(t)
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A new, empty directory for a test, with the given files written into it.
+    fn test_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("scli-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        for (file, content) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn merges_included_files() {
+        let dir = test_dir(
+            "include",
+            &[
+                ("scli.commands.yaml", "- include: parts/*.yaml\n- category: Demo\n  commands:\n    - name: main\n      exec: echo main\n"),
+                ("parts/a.yaml", "- category: Demo\n  commands:\n    - name: a\n      exec: echo a\n"),
+                ("parts/b.yaml", "- category: Demo\n  subcategories:\n    - name: sub\n      commands:\n        - name: b\n          exec: echo b\n"),
+                ("scli.commands.d/c.yaml", "- category: Other\n  commands:\n    - name: c\n      exec: echo c\n"),
+            ],
+        );
+
+        let sources = load_commands(&dir.join("scli.commands.yaml")).unwrap();
+        let mut categories = Vec::new();
+        let mut defined_in = BTreeMap::new();
+        merge_categories(&mut categories, sources, &mut defined_in);
+
+        let names: Vec<&str> = categories.iter().map(|c| c.category.as_str()).collect();
+        assert_eq!(names, ["Demo", "Other"]);
+        let demo: Vec<&str> = categories[0].commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(demo, ["a", "main"]);
+        assert_eq!(categories[0].subcategories[0].commands[0].name, "b");
+        assert_eq!(defined_in["demo.a"], dir.join("parts/a.yaml"));
+        assert_eq!(defined_in["demo.sub.b"], dir.join("parts/b.yaml"));
+        assert_eq!(defined_in["demo.main"], dir.join("scli.commands.yaml"));
+        assert_eq!(defined_in["other.c"], dir.join("scli.commands.d/c.yaml"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reports_both_files_of_a_duplicate_command() {
+        let dir = test_dir(
+            "duplicate",
+            &[
+                ("scli.commands.yaml", "- include: [one.yaml, two.yaml]\n"),
+                ("one.yaml", "- category: Demo\n  commands:\n    - name: deploy\n      exec: echo one\n"),
+                ("two.yaml", "- category: demo\n  commands:\n    - name: Deploy\n      exec: echo two\n"),
+            ],
+        );
+
+        match load_commands(&dir.join("scli.commands.yaml")) {
+            Err(ConfigLoadError::DuplicateCommand(name, first, second)) => {
+                assert_eq!(name, "demo.deploy");
+                assert_eq!(first, dir.join("one.yaml"));
+                assert_eq!(second, dir.join("two.yaml"));
+            }
+            other => panic!("expected a duplicate command error, got {:?}", other.map(|_| ())),
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_missing_include() {
+        let dir = test_dir("missing", &[("scli.commands.yaml", "- include: nope.yaml\n")]);
+        assert!(matches!(
+            load_commands(&dir.join("scli.commands.yaml")),
+            Err(ConfigLoadError::InvalidInclude(..))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
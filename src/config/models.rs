@@ -1,12 +1,18 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::time::Duration;
 use serde_yaml::Value;
+use crate::exit_codes;
+use crate::utils::string::{format_duration, parse_duration};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ConfigFile {
     pub filename: String,
     pub path: PathBuf,
+    /** The config is valid without this file */
+    #[serde(default)]
+    pub optional: bool,
 }
 
 /** Global configuration loaded from config files */
@@ -14,8 +20,28 @@ pub struct ConfigFile {
 pub struct Config {
     pub default_group: Option<String>,
     pub params: HashMap<String, UserParams>,
+    /** Keys of group params whose values are secret (redacted when shown) */
+    pub secret_params: Vec<String>,
+    /** Where `{{ "name" | secret }}` and `scli secret` look up secrets */
+    pub secret_backend: SecretBackendConfig,
+    /** Shell or interpreter used for commands that don't set their own `shell` */
+    pub shell: Option<String>,
     pub categories: Vec<Category>,
-    pub files: HashMap<String, ConfigFile>
+    /** Files of the highest-precedence layer that has them */
+    pub files: HashMap<String, ConfigFile>,
+    /** Where commands and params came from (`scli config sources`) */
+    pub sources: ConfigSources,
+}
+
+/** Files the configuration was loaded from */
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ConfigSources {
+    /** Every file read, lowest precedence first */
+    pub files: Vec<PathBuf>,
+    /** Command path (e.g. `demo.basic`) -> file defining it */
+    pub commands: BTreeMap<String, PathBuf>,
+    /** `<group>.<param>` (and `<group>.env.<VAR>`) -> params file setting it */
+    pub params: BTreeMap<String, PathBuf>,
 }
 
 impl Default for Config {
@@ -23,27 +49,69 @@ impl Default for Config {
         Self {
             default_group: None,
             params: HashMap::new(),
+            secret_params: Vec::new(),
+            secret_backend: SecretBackendConfig::default(),
+            shell: None,
             categories: Vec::new(),
             files: HashMap::from([
-                ("paramsFile".to_string(), ConfigFile { filename: "scli.params.yaml".to_string(), path: PathBuf::new() }),
-                ("commandsFile".to_string(), ConfigFile { filename: "scli.commands.yaml".to_string(), path: PathBuf::new() })
+                ("paramsFile".to_string(), ConfigFile { filename: "scli.params.yaml".to_string(), path: PathBuf::new(), optional: true }),
+                ("commandsFile".to_string(), ConfigFile { filename: "scli.commands.yaml".to_string(), path: PathBuf::new(), optional: false })
             ]),
+            sources: ConfigSources::default(),
         }
     }
 }
 
+/** Secret store backend, e.g. `secret_backend: { type: pass, prefix: scli }` */
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SecretBackendConfig {
+    /** Encrypted file in the simple-cli config dir */
+    #[default]
+    Local,
+    /** The `pass` password manager, optionally below a folder */
+    Pass {
+        #[serde(default)]
+        prefix: Option<String>,
+    },
+    /** A helper command implementing `get`, `set`, `list` and `rm` */
+    Helper { command: String },
+}
+
 /**
 Runtime global context used across modules.
 */
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GlobalContext {
     pub current_group: Option<String>,
+    /** Render and show commands without executing them */
+    pub dry_run: bool,
+    /** With `dry_run`, write only the raw rendered command to stdout */
+    pub print_only: bool,
+    /** Never prompt; fail if input would be needed */
+    pub no_input: bool,
+    /** Skip confirmations (`--yes`) */
+    pub assume_yes: bool,
+    /** Prefix for every output line, set for commands running in parallel */
+    pub output_label: Option<String>,
+    /** Commands currently running, outermost first, for detecting cycles */
+    #[serde(skip)]
+    pub running: Vec<String>,
+    /** Params marked `secret` by the running commands, redacted in the commands they run too */
+    #[serde(skip)]
+    pub secret_params: Vec<String>,
 }
 
 /** User parameters with arbitrary key-value pairs (from scli.params.yaml groups).
-    All fields are accessible in templates via `config.<key>`. */
+    All fields are accessible in templates via `config.<key>`, except `env`, which holds
+    environment variables exported to every command run while the group is active, and
+    `protected`, which requires typing the group name before running any command. */
 #[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct UserParams {
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub protected: bool,
     #[serde(flatten)]
     pub fields: HashMap<String, Value>,
 }
@@ -76,11 +144,261 @@ pub struct CommandDef {
     pub name: String,
     #[serde(default)]
     pub description: String,
+    /** Command template; either `exec` or `steps` must be given */
+    #[serde(default)]
     pub exec: String,
+    /** Commands run one after the other, sharing the collected params */
+    #[serde(default)]
+    pub steps: Vec<StepDef>,
     #[serde(default)]
     pub params: Vec<ParamDef>,
+    /** Ask before running: `confirm: true`, or `confirm: "<question>"` */
+    #[serde(default)]
+    pub confirm: Option<ConfirmPrompt>,
+    /** Catalogue commands run first, by dotted name, optionally capturing their output */
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub pre_command: Vec<PreCommand>,
+    /** Catalogue commands run afterwards (also when the command fails), by dotted name */
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub post_command: Vec<String>,
+    /** Catalogue commands run after the command succeeded, before `post_command` */
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub on_success: Vec<String>,
+    /** Catalogue commands run after the command failed, before `post_command` */
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub on_failure: Vec<String>,
+    /** Environment variables set for the command (values are templates) */
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /** Working directory for the command (template) */
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /** Shell or interpreter for `exec`, e.g. `bash`, `pwsh` or `python3` (overrides the global `shell`) */
+    #[serde(default)]
+    pub shell: Option<String>,
+    /** Kill the command (and its process group) when it runs longer than this */
     #[serde(default)]
-    pub pre_command: Option<String>,
+    pub timeout: Option<DurationValue>,
+    /** Re-run the command when it fails */
+    #[serde(default)]
+    pub retry: Option<RetryPolicy>,
+}
+
+impl CommandDef {
+    /** All templates of this command (exec, steps, env values, cwd and the confirmation
+        question), used to discover parameters. */
+    pub fn templates(&self) -> Vec<&str> {
+        let mut templates = vec![self.exec.as_str()];
+        templates.extend(self.steps.iter().map(|s| s.exec.as_str()));
+        let mut env_keys: Vec<&String> = self.env.keys().collect();
+        env_keys.sort();
+        templates.extend(env_keys.into_iter().map(|k| self.env[k].as_str()));
+        templates.extend(self.cwd.as_deref());
+        templates.extend(self.confirm.as_ref().and_then(ConfirmPrompt::question));
+        templates
+    }
+
+    /** The `when` conditions of the steps (Tera expressions), used to discover parameters. */
+    pub fn conditions(&self) -> Vec<&str> {
+        self.steps.iter().filter_map(|s| s.when.as_deref()).collect()
+    }
+
+    /** Names of the variables set from captured output (by steps and the pre-command). */
+    pub fn captured_names(&self) -> Vec<&str> {
+        let pre = self.pre_command.iter().filter_map(PreCommand::capture);
+        self.steps
+            .iter()
+            .filter_map(|s| s.capture.as_ref())
+            .chain(pre)
+            .map(Capture::name)
+            .collect()
+    }
+}
+
+/** Confirmation before running a command: `confirm: true` or `confirm: "Deploy to production?"` */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ConfirmPrompt {
+    Enabled(bool),
+    Question(String),
+}
+
+impl ConfirmPrompt {
+    /** The question to ask, or None if confirmation is disabled (`confirm: false`). */
+    pub fn question(&self) -> Option<&str> {
+        match self {
+            ConfirmPrompt::Enabled(true) => Some("Run this command?"),
+            ConfirmPrompt::Enabled(false) => None,
+            ConfirmPrompt::Question(question) => Some(question),
+        }
+    }
+}
+
+/** Accept either a single value or a list of values. */
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/** Pre-command: `pre_command: "<dotted name>"` or `pre_command: { command: "<dotted name>", capture: var }` */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PreCommand {
+    Name(String),
+    Detailed {
+        command: String,
+        #[serde(default)]
+        capture: Option<Capture>,
+    },
+}
+
+impl PreCommand {
+    pub fn command(&self) -> &str {
+        match self {
+            PreCommand::Name(name) => name,
+            PreCommand::Detailed { command, .. } => command,
+        }
+    }
+
+    pub fn capture(&self) -> Option<&Capture> {
+        match self {
+            PreCommand::Name(_) => None,
+            PreCommand::Detailed { capture, .. } => capture.as_ref(),
+        }
+    }
+}
+
+/** Store a command's stdout in a template variable: `capture: var` (trimmed text) or
+    `capture: { name: var, json: true }` (parsed as JSON, e.g. `{{ var.items[0].id }}`). */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Capture {
+    Name(String),
+    Detailed {
+        name: String,
+        #[serde(default)]
+        json: bool,
+    },
+}
+
+impl Capture {
+    pub fn name(&self) -> &str {
+        match self {
+            Capture::Name(name) => name,
+            Capture::Detailed { name, .. } => name,
+        }
+    }
+
+    pub fn json(&self) -> bool {
+        match self {
+            Capture::Name(_) => false,
+            Capture::Detailed { json, .. } => *json,
+        }
+    }
+}
+
+/** One step of a multi-step command */
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct StepDef {
+    /** Shown in the step header and summary (defaults to `step <n>`) */
+    #[serde(default)]
+    pub name: Option<String>,
+    /** Command template */
+    #[serde(default)]
+    pub exec: String,
+    /** Catalogue commands to run at the same time, instead of `exec` */
+    #[serde(default)]
+    pub parallel: Vec<String>,
+    /** Carry on with the next step if this one fails */
+    #[serde(default)]
+    pub continue_on_error: bool,
+    /** Tera condition, e.g. `params.region == "eu"`; the step is skipped when it is false */
+    #[serde(default)]
+    pub when: Option<String>,
+    /** Store the step's stdout in a variable for later steps instead of showing it */
+    #[serde(default)]
+    pub capture: Option<Capture>,
+}
+
+/** Retry policy, e.g. `retry: { attempts: 3, delay: 2s, backoff: 2, on_exit_codes: [1, 124] }` */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /** Total number of runs, including the first */
+    #[serde(default = "RetryPolicy::default_attempts")]
+    pub attempts: u32,
+    /** Wait before the first retry */
+    #[serde(default = "RetryPolicy::default_delay")]
+    pub delay: DurationValue,
+    /** Factor applied to the delay after each retry (1 keeps it constant, 2 doubles it) */
+    #[serde(default = "RetryPolicy::default_backoff")]
+    pub backoff: f64,
+    /** Exit codes that trigger a retry (a timeout counts as 124). When empty, any failure
+        except an interrupt (Ctrl-C, 130) is retried. */
+    #[serde(default)]
+    pub on_exit_codes: Vec<i32>,
+}
+
+impl RetryPolicy {
+    fn default_attempts() -> u32 {
+        3
+    }
+
+    fn default_delay() -> DurationValue {
+        DurationValue(Duration::from_secs(1))
+    }
+
+    fn default_backoff() -> f64 {
+        1.0
+    }
+
+    /** True if a run that ended with `code` should be retried. */
+    pub fn retries(&self, code: i32) -> bool {
+        if self.on_exit_codes.is_empty() {
+            code != 0 && code != exit_codes::INTERRUPTED
+        } else {
+            self.on_exit_codes.contains(&code)
+        }
+    }
+}
+
+/** A duration written as `30s`, `5m`, `1h30m`, `500ms` or a plain number of seconds */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DurationValue(pub Duration);
+
+impl Serialize for DurationValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_duration(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for DurationValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Seconds(f64),
+            Text(String),
+        }
+
+        let duration = match Raw::deserialize(deserializer)? {
+            Raw::Seconds(seconds) => parse_duration(&seconds.to_string()),
+            Raw::Text(text) => parse_duration(&text),
+        };
+        duration.map(DurationValue).map_err(serde::de::Error::custom)
+    }
 }
 
 /** Argument definition for a command */
@@ -93,4 +411,72 @@ pub struct ParamDef {
     pub optional: bool,
     #[serde(default)]
     pub default: Option<String>,
+    /** Fixed list of accepted values; prompts become a selection list */
+    #[serde(default)]
+    pub choices: Vec<String>,
+    /** Shell command whose output provides the accepted values */
+    #[serde(default)]
+    pub choices_from: Option<ChoicesFrom>,
+    /** Accept several values; exposed to templates as a list */
+    #[serde(default)]
+    pub multi: bool,
+    /** Value type, used for validation and for the value passed to templates */
+    #[serde(default, rename = "type")]
+    pub kind: ParamType,
+    /** Regular expression the whole value must match */
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /** Lower bound for `int` and `float` values */
+    #[serde(default)]
+    pub min: Option<f64>,
+    /** Upper bound for `int` and `float` values */
+    #[serde(default)]
+    pub max: Option<f64>,
+    /** Collect with masked input and redact the value wherever the command is shown */
+    #[serde(default)]
+    pub secret: bool,
+}
+
+/** Parameter value types */
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    #[default]
+    String,
+    Int,
+    Float,
+    Bool,
+    Path,
+    Url,
+    Enum,
+}
+
+/** Dynamic choices source: a shell command, optionally with a JSON path into its output.
+    Either `choices_from: "<command>"` (one choice per stdout line) or
+    `choices_from: { command: "<command>", json_path: "items[*].name" }`. */
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ChoicesFrom {
+    Command(String),
+    Detailed {
+        command: String,
+        #[serde(default)]
+        json_path: Option<String>,
+    },
+}
+
+impl ChoicesFrom {
+    pub fn command(&self) -> &str {
+        match self {
+            ChoicesFrom::Command(cmd) => cmd,
+            ChoicesFrom::Detailed { command, .. } => command,
+        }
+    }
+
+    pub fn json_path(&self) -> Option<&str> {
+        match self {
+            ChoicesFrom::Command(_) => None,
+            ChoicesFrom::Detailed { json_path, .. } => json_path.as_deref(),
+        }
+    }
 }
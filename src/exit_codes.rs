@@ -0,0 +1,34 @@
+/*! Process exit codes used by scli. A command that runs and fails exits with the child's own code. */
+
+/** Success */
+pub const SUCCESS: i32 = 0;
+
+/** A built-in action (e.g. `scli secret get`) failed */
+pub const FAILURE: i32 = 1;
+
+/** Unknown command or invalid command-line usage */
+pub const USAGE: i32 = 64;
+
+/** A parameter value failed validation */
+pub const INVALID_PARAM: i32 = 65;
+
+/** Required parameters were not provided */
+pub const MISSING_PARAMS: i32 = 66;
+
+/** The command template failed to render */
+pub const RENDER: i32 = 70;
+
+/** The command could not be started */
+pub const EXECUTE: i32 = 71;
+
+/** A command needing confirmation was declined, or could not be confirmed (e.g. with --no-input) */
+pub const NOT_CONFIRMED: i32 = 77;
+
+/** Configuration files could not be found or loaded */
+pub const CONFIG: i32 = 78;
+
+/** The command was killed after exceeding its `timeout` (as with `timeout(1)`) */
+pub const TIMEOUT: i32 = 124;
+
+/** The command was interrupted with Ctrl-C (128 + SIGINT) */
+pub const INTERRUPTED: i32 = 130;
@@ -1,7 +1,7 @@
 /** Interactive menu runner. */
 use crate::config::models::{Config, GlobalContext, CommandDef};
-use crate::commands::{find_command, run_command};
-use crate::commands::arguments::substitute_parameters;
+use crate::commands::run_command_chain;
+use crate::commands::arguments::{substitute_parameters, ParamSource};
 use dialoguer::{Select, console::Term};
 
 #[derive(Clone, Debug)]
@@ -16,6 +16,7 @@ enum MenuItem {
     NavigateCategory(usize),
     NavigateSubCategory(usize, usize),
     ExecuteCommand(usize, Option<usize>, usize),
+    ToggleDryRun,
     GoBack,
     Exit,
     Header, // Non-interactive header row
@@ -49,7 +50,9 @@ impl Menu {
         self.add(label, MenuItem::Header);
     }
 
-    fn add_navigation_actions(&mut self, at_root: bool) {
+    fn add_navigation_actions(&mut self, at_root: bool, dry_run: bool) {
+        let dry_run_label = if dry_run { "on" } else { "off" };
+        self.add(format!("⚙ Dry run: {}", dry_run_label), MenuItem::ToggleDryRun);
         if !at_root {
             self.add("◀ Go Back".to_string(), MenuItem::GoBack);
         }
@@ -67,7 +70,7 @@ pub fn run_interactive(
     let mut current_level = MenuLevel::Root;
 
     loop {
-        let menu = build_menu_for_level(&current_level, cfg);
+        let menu = build_menu_for_level(&current_level, cfg, ctx);
         let prompt = create_prompt_for_level(&current_level, cfg);
 
         match present_menu_and_get_selection(&menu, &prompt) {
@@ -95,20 +98,24 @@ pub fn run_interactive(
 }
 
 /// Build menu items for the current navigation level
-fn build_menu_for_level(level: &MenuLevel, cfg: &Config) -> Menu {
+fn build_menu_for_level(level: &MenuLevel, cfg: &Config, ctx: &GlobalContext) -> Menu {
     match level {
-        MenuLevel::Root => build_root_menu(cfg),
-        MenuLevel::Category(ci) => build_category_menu(*ci, cfg),
-        MenuLevel::SubCategory(ci, si) => build_subcategory_menu(*ci, *si, cfg),
+        MenuLevel::Root => build_root_menu(cfg, ctx),
+        MenuLevel::Category(ci) => build_category_menu(*ci, cfg, ctx),
+        MenuLevel::SubCategory(ci, si) => build_subcategory_menu(*ci, *si, cfg, ctx),
     }
 }
 
-fn build_root_menu(cfg: &Config) -> Menu {
+fn build_root_menu(cfg: &Config, ctx: &GlobalContext) -> Menu {
     let mut menu = Menu::new();
 
-    // Add categories
+    // Add categories; commands outside of any category are listed directly
     for (idx, category) in cfg.categories.iter().enumerate() {
-        menu.add(category.category.clone(), MenuItem::NavigateCategory(idx));
+        if category.category.trim().is_empty() {
+            add_commands_to_menu(&mut menu, &category.commands, idx, None);
+        } else {
+            menu.add(category.category.clone(), MenuItem::NavigateCategory(idx));
+        }
     }
 
     if menu.labels.is_empty() {
@@ -116,12 +123,12 @@ fn build_root_menu(cfg: &Config) -> Menu {
     }
 
     // Add actions
-    menu.add_navigation_actions(true);
+    menu.add_navigation_actions(true, ctx.dry_run);
 
     menu
 }
 
-fn build_category_menu(category_idx: usize, cfg: &Config) -> Menu {
+fn build_category_menu(category_idx: usize, cfg: &Config, ctx: &GlobalContext) -> Menu {
     let mut menu = Menu::new();
     let category = &cfg.categories[category_idx];
 
@@ -138,11 +145,11 @@ fn build_category_menu(category_idx: usize, cfg: &Config) -> Menu {
         add_commands_to_menu(&mut menu, &category.commands, category_idx, None);
     }
 
-    menu.add_navigation_actions(false);
+    menu.add_navigation_actions(false, ctx.dry_run);
     menu
 }
 
-fn build_subcategory_menu(category_idx: usize, subcategory_idx: usize, cfg: &Config) -> Menu {
+fn build_subcategory_menu(category_idx: usize, subcategory_idx: usize, cfg: &Config, ctx: &GlobalContext) -> Menu {
     let mut menu = Menu::new();
     let subcategory = &cfg.categories[category_idx].subcategories[subcategory_idx];
 
@@ -151,7 +158,7 @@ fn build_subcategory_menu(category_idx: usize, subcategory_idx: usize, cfg: &Con
         add_commands_to_menu(&mut menu, &subcategory.commands, category_idx, Some(subcategory_idx));
     }
 
-    menu.add_navigation_actions(false);
+    menu.add_navigation_actions(false, ctx.dry_run);
     menu
 }
 
@@ -429,6 +436,10 @@ fn handle_menu_selection(
         MenuItem::Header => MenuAction::Stay,
         MenuItem::Exit => MenuAction::Exit,
         MenuItem::GoBack => MenuAction::Navigate(navigate_back(current_level)),
+        MenuItem::ToggleDryRun => {
+            ctx.dry_run = !ctx.dry_run;
+            MenuAction::Stay
+        }
         MenuItem::NavigateCategory(idx) => MenuAction::Navigate(MenuLevel::Category(idx)),
         MenuItem::NavigateSubCategory(ci, si) => MenuAction::Navigate(MenuLevel::SubCategory(ci, si)),
         MenuItem::ExecuteCommand(ci, maybe_si, ki) => {
@@ -458,18 +469,7 @@ fn handle_command_selection(
         None => &cfg.categories[category_idx].commands[command_idx],
     };
 
-    // Execute pre-command if specified
-    if let Some(pre_cmd_name) = &cmd.pre_command {
-        match find_command(cfg.categories.as_slice(), pre_cmd_name) {
-            Some(pre_cmd) => {
-                println!("Running pre-command: {} (`{}`)", pre_cmd.name, pre_cmd.exec);
-                handle_command_execution(cfg, ctx, pre_cmd)
-            }
-            None => eprintln!("Pre-command '{}' not found", pre_cmd_name),
-        }
-    }
-
-    // Execute the main command
+    // Execute the command with its pre- and post-commands
     handle_command_execution(cfg, ctx, cmd);
     // Spacer between command output and the next interactive menu
     println!();
@@ -480,8 +480,14 @@ fn handle_command_execution(
     ctx: &mut GlobalContext,
     cmd: &CommandDef,
 ){
-    let args = substitute_parameters(cmd, None, true);
-    if let Err(e) = run_command(cmd, cfg, ctx, &args){
+    let args = match substitute_parameters(cmd, None, true, &ParamSource::new(cfg, ctx)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    if let Err(e) = run_command_chain(cmd, cfg, ctx, &args, true){
         eprintln!("Failed to execute command: {}", e)
     }
-}
\ No newline at end of file
+}
@@ -1,68 +1,120 @@
 mod cli;
 mod commands;
 mod config;
+mod exit_codes;
 mod interactive;
+mod secrets;
 mod utils;
 
-use crate::commands::cli_utils::{handle_switch_subscription, list_commands};
+use crate::commands::cli_utils::{handle_secret_command, handle_switch_subscription, list_commands, print_config_sources, NO_GROUPS};
 use clap::Parser;
-use cli::Cli;
-use commands::{find_command, run_command};
+use cli::{Cli, ConfigAction, SubCommand};
+use commands::{find_command, run_command_chain, CommandError};
 use config::{
-    create_context, load_config, Config,
+    create_context, load_config, Config, ConfigOverrides,
 };
 use interactive::run_interactive;
 use crate::utils::io::clear_saved_data;
-use serde_yaml;
-use std::collections::HashMap;
-use std::path::PathBuf;
-use log::warn;
-use crate::commands::arguments::substitute_parameters;
+use crate::commands::arguments::{parse_param_overrides, substitute_parameters, ParamSource};
+use crate::commands::groups::{resolve_groups, run_in_groups};
+use crate::commands::jobs::{report_jobs, run_command_jobs, CommandJob};
 
-/// Entry point: init: load config and initialize context
+/// Entry point: init: parse CLI, load config and initialize context
 fn main() {
     // init logger
     env_logger::init();
 
+    //parse CLI
+    let cli = Cli::parse();
+
     // loqd values from files
-    let config = load_config().expect("Failed to load config");
+    let overrides = ConfigOverrides {
+        dir: cli.config_dir.clone(),
+        commands_file: cli.commands_file.clone(),
+        params_file: cli.params_file.clone(),
+    };
+    let config = match load_config(&overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load config: {}", e);
+            std::process::exit(exit_codes::CONFIG);
+        }
+    };
 
     // Initialize global context
     let mut global_ctx = create_context(&config);
 
     // Handle commands
-    handle_args(&config, &mut global_ctx);
+    let code = handle_args(cli, &config, &mut global_ctx);
+    std::process::exit(code);
 }
 
-/// parse CLI and dispatch to interactive or directly execute.
-fn handle_args(config: &Config, mut global_ctx: &mut config::GlobalContext) {
-    //parse CLI
-    let cli = Cli::parse();
+/// Dispatch to interactive or directly execute. Returns the process exit code.
+fn handle_args(cli: Cli, config: &Config, global_ctx: &mut config::GlobalContext) -> i32 {
+    global_ctx.dry_run = cli.dry_run || cli.print;
+    global_ctx.print_only = cli.print;
+    global_ctx.assume_yes = cli.yes;
+    global_ctx.no_input = cli.no_input;
 
     // Clear stored data and exit
     if cli.clear_stored {
-        match clear_saved_data() {
-            Ok(_) => println!("Cleared stored data."),
-            Err(e) => eprintln!("Failed to clear stored data: {}", e),
-        }
-        return;
+        return match clear_saved_data() {
+            Ok(_) => {
+                println!("Cleared stored data.");
+                exit_codes::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("Failed to clear stored data: {}", e);
+                exit_codes::FAILURE
+            }
+        };
+    }
+
+    // Built-in subcommands
+    if let Some(SubCommand::Run { commands }) = &cli.subcommand {
+        return exit_code_of(run_commands(commands, &cli, config, global_ctx));
+    }
+    if let Some(SubCommand::Config { action: ConfigAction::Sources }) = &cli.subcommand {
+        print_config_sources(config);
+        return exit_codes::SUCCESS;
+    }
+    if let Some(SubCommand::Secret { action }) = &cli.subcommand {
+        return match handle_secret_command(config, action, !cli.no_input) {
+            Ok(()) => exit_codes::SUCCESS,
+            Err(e) => {
+                eprintln!("{}", e);
+                exit_codes::FAILURE
+            }
+        };
     }
 
     // Show config and exit
     if cli.show_active_params {
-        let active_group = global_ctx.current_group.as_ref().unwrap();
+        // Commands-only setups have no params file, or one without groups
+        let params_file = config.files.get("paramsFile").filter(|_| !config.params.is_empty());
+        let Some(params_file) = params_file else {
+            println!("{}", NO_GROUPS);
+            return exit_codes::SUCCESS;
+        };
 
-        let path = match config.files.get("paramsFile") {
-            Some(file) => file.path.clone(),
-            None => {
-                warn!("paramsFile is missing in config");
-                PathBuf::from("scli.params.yaml")
-            }
+        println!("Params file: {}", params_file.path.display());
+
+        let Some(active_group) = global_ctx.current_group.as_ref() else {
+            println!("No active group. Select one with --switch-param-group.");
+            return exit_codes::SUCCESS;
         };
 
-        println!("Params file: {}", path.display());
+        // Hide values of secret params
+        let mut shown_params = config.params.get(active_group).cloned();
+        if let Some(params) = shown_params.as_mut() {
+            for key in &config.secret_params {
+                if let Some(value) = params.fields.get_mut(key) {
+                    *value = serde_yaml::Value::from("****");
+                }
+            }
+        }
 
-        match serde_yaml::to_string(&config.params.get(active_group)) {
+        match serde_yaml::to_string(&shown_params) {
             Ok(subs_yaml) => {
                 println!("Active group: {}", active_group);
                 println!("Params:\n  {}", subs_yaml.replace("\n", "\n  "));
@@ -71,54 +123,103 @@ fn handle_args(config: &Config, mut global_ctx: &mut config::GlobalContext) {
                 eprintln!("Failed to serialize groups to YAML: {}", e);
             }
         }
-        return;
+        return exit_codes::SUCCESS;
     }
 
     // Switch subscription
     if cli.switch_param_group {
-        handle_switch_subscription(config, &mut global_ctx);
-        return;
+        handle_switch_subscription(config, global_ctx);
+        return exit_codes::SUCCESS;
     }
 
     // List commands
     if cli.list_cmds {
-        list_commands(&config);
-        return;
+        list_commands(config);
+        return exit_codes::SUCCESS;
     }
 
     // Run interactive mode
     if cli.interactive {
-        run_interactive(&config, &mut global_ctx);
-        return;
+        run_interactive(config, global_ctx);
+        return exit_codes::SUCCESS;
     }
 
     // Run direct command
-    if let Some(cmd_name) = cli.command {
-        match find_command(&config.categories, &cmd_name) {
-            Some(cmd) => {
-
-                // Build param overrides from cli.param
-                let mut param_overrides: HashMap<String, String> = HashMap::new();
-                for param in &cli.param {
-                    if let Some((k, v)) = param.split_once('=') {
-                        param_overrides.insert(k.to_string(), v.to_string());
-                    }
-                }
+    if let Some(cmd_name) = &cli.command {
+        return exit_code_of(run_direct_command(cmd_name, &cli, config, global_ctx));
+    }
 
-                let params_parsed = substitute_parameters(
-                    cmd,
-                    Some(&param_overrides),
-                    false
-                );
+    println!("No command given. Try --interactive, --list, or specify a command.");
+    exit_codes::USAGE
+}
 
-                if let Err(e) = run_command(cmd, config, &mut global_ctx, &params_parsed) {
-                    eprintln!("Failed to execute command: {}", e);
-                }
+/// Report the error of a command run, if any, and return the process exit code.
+fn exit_code_of(result: Result<(), CommandError>) -> i32 {
+    match result {
+        Ok(()) => exit_codes::SUCCESS,
+        Err(e) => {
+            // the child has already reported its own failure
+            if !matches!(e, CommandError::Failed(_)) {
+                eprintln!("{}", e);
             }
-            None => eprintln!("Unknown command: {}", cmd_name),
+            e.exit_code()
         }
-        return;
     }
+}
 
-    println!("No command given. Try --interactive, --list, or specify a command.");
+/// Find a command by name, collect its params from the CLI and run it, in the active
+/// group or in each group given with --groups / --all-groups.
+/// Missing params are prompted for with `prompt_missing`, otherwise they are an error.
+fn run_direct_command(
+    cmd_name: &str,
+    cli: &Cli,
+    config: &Config,
+    global_ctx: &mut config::GlobalContext,
+) -> Result<(), CommandError> {
+    let cmd = find_command(&config.categories, cmd_name)
+        .ok_or_else(|| CommandError::UnknownCommand(cmd_name.to_string()))?;
+
+    // Check the groups before asking for any params
+    let fan_out = !cli.groups.is_empty() || cli.all_groups;
+    let groups = if fan_out { resolve_groups(config, &cli.groups, cli.all_groups)? } else { Vec::new() };
+
+    // Build param overrides from cli.param
+    let param_overrides = parse_param_overrides(&cli.param)?;
+
+    // Params with `choices_from` are collected in each group (see run_in_groups)
+    let source = ParamSource { defer_group_choices: fan_out, ..ParamSource::new(config, global_ctx) };
+    let params_parsed = substitute_parameters(cmd, Some(&param_overrides), cli.prompt_missing, &source)?;
+
+    if fan_out {
+        return run_in_groups(cmd, config, global_ctx, &params_parsed, &groups, cli.parallel, cli.prompt_missing);
+    }
+    run_command_chain(cmd, config, global_ctx, &params_parsed, cli.prompt_missing)
+}
+
+/// Run several commands by name (`scli run a b c`), one after the other or with --parallel
+/// at the same time, then print a table of the results. Params of all of them are
+/// collected from the CLI before any of them runs.
+fn run_commands(
+    cmd_names: &[String],
+    cli: &Cli,
+    config: &Config,
+    global_ctx: &config::GlobalContext,
+) -> Result<(), CommandError> {
+    let param_overrides = parse_param_overrides(&cli.param)?;
+
+    let mut jobs: Vec<CommandJob> = Vec::new();
+    for cmd_name in cmd_names {
+        let cmd = find_command(&config.categories, cmd_name)
+            .ok_or_else(|| CommandError::UnknownCommand(cmd_name.to_string()))?;
+        let args = substitute_parameters(
+            cmd,
+            Some(&param_overrides),
+            cli.prompt_missing,
+            &ParamSource::new(config, global_ctx),
+        )?;
+        jobs.push(CommandJob { name: cmd_name.clone(), cmd, ctx: global_ctx.clone(), args });
+    }
+
+    let results = run_command_jobs("command", &jobs, config, cli.parallel, cli.prompt_missing);
+    report_jobs("command", &results, global_ctx)
 }
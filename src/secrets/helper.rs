@@ -0,0 +1,74 @@
+/**
+ Secret backend delegating to an arbitrary helper command, in the style of git credential helpers.
+ The helper is called as `<command> get <key>`, `<command> set <key>` (value on stdin),
+ `<command> list` and `<command> rm <key>`. `get` and `rm` exit non-zero if the secret does not exist.
+*/
+use super::{SecretBackend, SecretError};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+pub struct HelperBackend {
+    command: String,
+}
+
+impl HelperBackend {
+    pub fn new(command: String) -> Self {
+        Self { command }
+    }
+
+    /** Run the helper through the shell, passing the action and key as positional arguments. */
+    fn run(&self, args: &[&str], input: Option<&str>) -> Result<Output, SecretError> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", self.command))
+            .arg("scli-secret-helper")
+            .args(args)
+            .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| SecretError::Backend(format!("Failed to run `{}`: {}", self.command, e)))?;
+
+        if let (Some(value), Some(mut stdin)) = (input, child.stdin.take()) {
+            stdin.write_all(value.as_bytes())?;
+        }
+
+        Ok(child.wait_with_output()?)
+    }
+}
+
+impl SecretBackend for HelperBackend {
+    fn get(&self, key: &str) -> Result<Option<String>, SecretError> {
+        let output = self.run(&["get", key], None)?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(Some(stdout.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), SecretError> {
+        let output = self.run(&["set", key], Some(value))?;
+        if !output.status.success() {
+            return Err(SecretError::Backend(format!("`{} set` exited with {}", self.command, output.status)));
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, SecretError> {
+        let output = self.run(&["list"], None)?;
+        if !output.status.success() {
+            return Err(SecretError::Backend(format!("`{} list` exited with {}", self.command, output.status)));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn remove(&self, key: &str) -> Result<bool, SecretError> {
+        Ok(self.run(&["rm", key], None)?.status.success())
+    }
+}
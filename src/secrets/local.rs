@@ -0,0 +1,191 @@
+/** Local secret store: a passphrase-encrypted file in the `simple-cli` config dir. */
+use super::{SecretBackend, SecretError};
+use crate::config::config_data_dir;
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use dialoguer::Password;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const STORE_FILE: &str = "secrets.enc";
+
+/** Environment variable holding the store passphrase (prompted for when unset). */
+pub const PASSPHRASE_ENV: &str = "SIMPLE_CLI_SECRET_PASSPHRASE";
+
+/** Passphrase cache so it is only asked for once per run. Held while prompting, so
+    commands running in parallel never prompt at the same time. */
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+
+/** On-disk format: secrets serialized as JSON, encrypted with ChaCha20-Poly1305
+    using a key derived from the passphrase with Argon2. */
+#[derive(Serialize, Deserialize)]
+struct EncryptedStore {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub struct LocalBackend {
+    path: PathBuf,
+    /** The passphrase may be prompted for (not with `--no-input`) */
+    allow_prompts: bool,
+}
+
+impl LocalBackend {
+    pub fn new(allow_prompts: bool) -> Self {
+        Self { path: config_data_dir().join(STORE_FILE), allow_prompts }
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>, SecretError> {
+        if !self.path.exists() {
+            debug!("Secret store {:?} does not exist yet", self.path);
+            return Ok(BTreeMap::new());
+        }
+
+        let content = fs::read_to_string(&self.path)?;
+        let store: EncryptedStore = serde_json::from_str(&content)
+            .map_err(|e| SecretError::Store(format!("Invalid secret store file {:?}: {}", self.path, e)))?;
+
+        let salt = decode(&store.salt)?;
+        let nonce = decode(&store.nonce)?;
+        let ciphertext = decode(&store.ciphertext)?;
+        if nonce.len() != 12 {
+            return Err(SecretError::Store("Invalid nonce in secret store".to_string()));
+        }
+
+        let cipher = ChaCha20Poly1305::new(&derive_key(&passphrase(false, self.allow_prompts)?, &salt)?);
+        let plain = cipher
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| SecretError::Store("Failed to decrypt the secret store (wrong passphrase?)".to_string()))?;
+
+        serde_json::from_slice(&plain)
+            .map_err(|e| SecretError::Store(format!("Corrupt secret store contents: {}", e)))
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<(), SecretError> {
+        let plain = serde_json::to_vec(secrets)
+            .map_err(|e| SecretError::Store(e.to_string()))?;
+
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = ChaCha20Poly1305::new(&derive_key(&passphrase(!self.path.exists(), self.allow_prompts)?, &salt)?);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plain.as_ref())
+            .map_err(|_| SecretError::Store("Failed to encrypt the secret store".to_string()))?;
+
+        let store = EncryptedStore {
+            version: 1,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+        let content = serde_json::to_string_pretty(&store)
+            .map_err(|e| SecretError::Store(e.to_string()))?;
+
+        write_private(&self.path, &content)?;
+        debug!("Saved secret store {:?}", self.path);
+        Ok(())
+    }
+}
+
+impl SecretBackend for LocalBackend {
+    fn get(&self, key: &str) -> Result<Option<String>, SecretError> {
+        Ok(self.load()?.remove(key))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), SecretError> {
+        let mut secrets = self.load()?;
+        secrets.insert(key.to_string(), value.to_string());
+        self.save(&secrets)
+    }
+
+    fn list(&self) -> Result<Vec<String>, SecretError> {
+        Ok(self.load()?.into_keys().collect())
+    }
+
+    fn remove(&self, key: &str) -> Result<bool, SecretError> {
+        let mut secrets = self.load()?;
+        let removed = secrets.remove(key).is_some();
+        if removed {
+            self.save(&secrets)?;
+        }
+        Ok(removed)
+    }
+}
+
+/**
+ Get the passphrase from the environment or prompt for it (with confirmation for a new
+ store). Without a terminal, or when prompts are not allowed, it must be in the environment.
+*/
+fn passphrase(new_store: bool, allow_prompts: bool) -> Result<String, SecretError> {
+    let mut cached = PASSPHRASE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(p) = cached.as_ref() {
+        return Ok(p.clone());
+    }
+
+    let value = match std::env::var(PASSPHRASE_ENV) {
+        Ok(v) if !v.is_empty() => v,
+        _ if !allow_prompts || !std::io::stdin().is_terminal() => {
+            return Err(SecretError::Store(format!(
+                "The secret store passphrase cannot be prompted for; set {}",
+                PASSPHRASE_ENV
+            )));
+        }
+        _ => {
+            let mut prompt = Password::new().with_prompt("Secret store passphrase");
+            if new_store {
+                prompt = prompt.with_confirmation("Confirm passphrase", "Passphrases do not match");
+            }
+            prompt
+                .interact()
+                .map_err(|e| SecretError::Store(format!("Failed to read passphrase: {}", e)))?
+        }
+    };
+
+    Ok(cached.insert(value).clone())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, SecretError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| SecretError::Store(format!("Failed to derive key: {}", e)))?;
+    Ok(key)
+}
+
+fn decode(s: &str) -> Result<Vec<u8>, SecretError> {
+    BASE64
+        .decode(s)
+        .map_err(|e| SecretError::Store(format!("Invalid secret store encoding: {}", e)))
+}
+
+/** Write a file readable only by the current user. */
+fn write_private(path: &PathBuf, content: &str) -> Result<(), SecretError> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(content.as_bytes())?;
+    }
+
+    #[cfg(not(unix))]
+    fs::write(path, content)?;
+
+    Ok(())
+}
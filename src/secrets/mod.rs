@@ -0,0 +1,53 @@
+/** Secret storage: a pluggable backend trait with local, `pass` and helper-command implementations. */
+pub mod local;
+pub mod pass;
+pub mod helper;
+
+use crate::config::{Config, SecretBackendConfig};
+use std::sync::Arc;
+use thiserror::Error;
+
+pub use helper::HelperBackend;
+pub use local::LocalBackend;
+pub use pass::PassBackend;
+
+/// Error type for secret backends
+#[derive(Debug, Error)]
+pub enum SecretError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Secret '{0}' not found")]
+    NotFound(String),
+
+    #[error("Secret store error: {0}")]
+    Store(String),
+
+    #[error("Secret backend command failed: {0}")]
+    Backend(String),
+}
+
+/** A store of named secrets. */
+pub trait SecretBackend: Send + Sync {
+    /** Look up a secret; `Ok(None)` if it does not exist. */
+    fn get(&self, key: &str) -> Result<Option<String>, SecretError>;
+
+    /** Create or replace a secret. */
+    fn set(&self, key: &str, value: &str) -> Result<(), SecretError>;
+
+    /** Names of all stored secrets. */
+    fn list(&self) -> Result<Vec<String>, SecretError>;
+
+    /** Remove a secret; returns false if it did not exist. */
+    fn remove(&self, key: &str) -> Result<bool, SecretError>;
+}
+
+/** Create the backend selected by `secret_backend` in the params file (local store by default).
+    `allow_prompts` is false with `--no-input`, so the local store does not ask for its passphrase. */
+pub fn backend_for(cfg: &Config, allow_prompts: bool) -> Arc<dyn SecretBackend> {
+    match &cfg.secret_backend {
+        SecretBackendConfig::Local => Arc::new(LocalBackend::new(allow_prompts)),
+        SecretBackendConfig::Pass { prefix } => Arc::new(PassBackend::new(prefix.clone())),
+        SecretBackendConfig::Helper { command } => Arc::new(HelperBackend::new(command.clone())),
+    }
+}
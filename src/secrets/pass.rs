@@ -0,0 +1,118 @@
+/** Secret backend delegating to `pass`, the standard unix password manager. */
+use super::{SecretBackend, SecretError};
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+pub struct PassBackend {
+    prefix: Option<String>,
+}
+
+impl PassBackend {
+    pub fn new(prefix: Option<String>) -> Self {
+        Self { prefix }
+    }
+
+    /** Name of the entry in the password store, e.g. `scli/mysecret`. */
+    fn entry(&self, key: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}/{}", prefix.trim_end_matches('/'), key),
+            None => key.to_string(),
+        }
+    }
+
+    fn store_dir(&self) -> PathBuf {
+        let root = match env::var("PASSWORD_STORE_DIR") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => dirs::home_dir().unwrap_or_default().join(".password-store"),
+        };
+        match &self.prefix {
+            Some(prefix) => root.join(prefix),
+            None => root,
+        }
+    }
+}
+
+impl SecretBackend for PassBackend {
+    fn get(&self, key: &str) -> Result<Option<String>, SecretError> {
+        let output = Command::new("pass")
+            .args(["show", &self.entry(key)])
+            .output()
+            .map_err(|e| SecretError::Backend(format!("Failed to run pass: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("is not in the password store") {
+                return Ok(None);
+            }
+            return Err(SecretError::Backend(stderr.trim().to_string()));
+        }
+
+        // pass convention: the secret is the first line
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(Some(stdout.lines().next().unwrap_or_default().to_string()))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), SecretError> {
+        let mut child = Command::new("pass")
+            .args(["insert", "--multiline", "--force", &self.entry(key)])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| SecretError::Backend(format!("Failed to run pass: {}", e)))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(value.as_bytes())?;
+        }
+
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(SecretError::Backend(format!("pass insert exited with {}", status)));
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, SecretError> {
+        let root = self.store_dir();
+        let mut keys = Vec::new();
+        if root.exists() {
+            collect_entries(&root, &root, &mut keys)?;
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    fn remove(&self, key: &str) -> Result<bool, SecretError> {
+        if self.get(key)?.is_none() {
+            return Ok(false);
+        }
+
+        let status = Command::new("pass")
+            .args(["rm", "--force", &self.entry(key)])
+            .stdout(Stdio::null())
+            .status()
+            .map_err(|e| SecretError::Backend(format!("Failed to run pass: {}", e)))?;
+
+        if !status.success() {
+            return Err(SecretError::Backend(format!("pass rm exited with {}", status)));
+        }
+        Ok(true)
+    }
+}
+
+/** Recursively collect `*.gpg` entries below `dir`, relative to `root` and without the extension. */
+fn collect_entries(root: &Path, dir: &Path, keys: &mut Vec<String>) -> Result<(), SecretError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_entries(root, &path, keys)?;
+        } else if path.extension().is_some_and(|ext| ext == "gpg")
+            && let Ok(rel) = path.with_extension("").strip_prefix(root)
+        {
+            keys.push(rel.to_string_lossy().to_string());
+        }
+    }
+    Ok(())
+}
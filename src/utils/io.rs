@@ -25,12 +25,18 @@ pub fn load_last_value(key: &str) -> Option<String> {
     fs::read_to_string(path).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
 }
 
-/** Remove all stored app data (e.g., last-used values). */
+/** Remove stored app data (e.g., last-used values). The secret store is kept. */
 pub fn clear_saved_data() -> std::io::Result<()> {
     let mut dir = config_dir().unwrap_or_default();
     dir.push("simple-cli");
-    if dir.exists() {
-        fs::remove_dir_all(&dir)?;
+    if !dir.exists() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "txt") {
+            fs::remove_file(&path)?;
+        }
     }
     Ok(())
 }
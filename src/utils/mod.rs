@@ -1,4 +1,5 @@
 /** General utilities */
 pub mod io;
+pub mod output;
 pub mod shell;
 pub mod string;
@@ -0,0 +1,72 @@
+/** Labelled output for commands running in parallel */
+use colored::{Color, Colorize};
+use std::io::{BufRead, BufReader, Read};
+
+/** Label colours, assigned in turn */
+const LABEL_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::BrightRed,
+];
+
+/** Coloured labels (`name |`) for output of parallel runs, padded to the same width. */
+pub fn job_labels(names: &[String]) -> Vec<String> {
+    let width = names.iter().map(|n| n.chars().count()).max().unwrap_or(0);
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            format!("{:<width$} |", name, width = width)
+                .color(LABEL_COLORS[i % LABEL_COLORS.len()])
+                .to_string()
+        })
+        .collect()
+}
+
+/** Print text to stdout (or stderr), each line prefixed with the label if there is one. */
+pub fn print_labeled(label: Option<&str>, text: &str, to_stderr: bool) {
+    let text = match label {
+        Some(label) => text
+            .split('\n')
+            .map(|line| if line.is_empty() { label.to_string() } else { format!("{} {}", label, line) })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        None => text.to_string(),
+    };
+    // one call per block, so lines of concurrent runs do not interleave
+    if to_stderr {
+        eprintln!("{}", text);
+    } else {
+        println!("{}", text);
+    }
+}
+
+/** Print everything read from a child's stdout or stderr, line by line, with the label. */
+pub fn forward_lines(reader: impl Read, label: &str, to_stderr: bool) {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+        let text = String::from_utf8_lossy(&line);
+        print_labeled(Some(label), text.trim_end_matches(['\n', '\r']), to_stderr);
+        line.clear();
+    }
+}
+
+/** `println!` with every line prefixed by an optional label (`Option<&str>`). */
+macro_rules! outln {
+    ($label:expr, $($arg:tt)*) => {
+        $crate::utils::output::print_labeled($label, &format!($($arg)*), false)
+    };
+}
+
+/** `eprintln!` with every line prefixed by an optional label (`Option<&str>`). */
+macro_rules! errln {
+    ($label:expr, $($arg:tt)*) => {
+        $crate::utils::output::print_labeled($label, &format!($($arg)*), true)
+    };
+}
+
+pub(crate) use {errln, outln};
@@ -1,16 +1,443 @@
 /** Shell utilities */
-use std::process::Command;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use crate::utils::output::forward_lines;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-/** Execute a shell command using the system shell. */
-pub fn execute_shell_command(cmd: &str) -> Result<i32, String> {
-    #[cfg(target_os = "windows")]
-    let output = Command::new("cmd").args(["/C", cmd]).status();
+/** How often a command with a timeout is checked for completion */
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/** Time a timed-out command gets to exit after SIGTERM before it is killed */
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+/** Options applied to the spawned shell process. */
+#[derive(Debug, Clone, Default)]
+pub struct ExecOptions {
+    /** Extra environment variables, added to the inherited environment */
+    pub env: HashMap<String, String>,
+    /** Working directory (defaults to the current directory) */
+    pub cwd: Option<PathBuf>,
+    /** Shell or interpreter, optionally with arguments, e.g. `bash`, `bash -eo pipefail` or
+        `python3` (defaults to `sh`, or `cmd` on Windows) */
+    pub shell: Option<String>,
+    /** Kill the command's process group when it runs longer than this */
+    pub timeout: Option<Duration>,
+    /** Prefix every output line with this label; the command gets no input (parallel runs) */
+    pub label: Option<String>,
+}
+
+/** Outcome of a command run with `execute_shell_command`. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecStatus {
+    /** The command finished with this exit code */
+    Exited(i32),
+    /** The command exceeded its timeout and was killed */
+    TimedOut,
+}
+
+/** Temporary script in its own private directory; both are removed when dropped. */
+struct TempScript {
+    dir: PathBuf,
+    path: PathBuf,
+}
+
+impl Drop for TempScript {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/** Program name of an interpreter without directory and extension, e.g. `/usr/bin/python3` -> `python3`. */
+fn interpreter_name(program: &str) -> String {
+    Path::new(program)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/** Flag that makes the interpreter run the next argument as code. */
+fn inline_flag(name: &str) -> &'static str {
+    match name {
+        "node" | "nodejs" | "perl" | "ruby" => "-e",
+        "pwsh" | "powershell" => "-Command",
+        "cmd" => "/C",
+        _ => "-c",
+    }
+}
+
+/** File extension for a script run by the interpreter (pwsh and cmd refuse unknown extensions). */
+fn script_extension(name: &str) -> &'static str {
+    match name {
+        n if n.starts_with("python") => "py",
+        "node" | "nodejs" => "js",
+        "pwsh" | "powershell" => "ps1",
+        "cmd" => "bat",
+        "perl" => "pl",
+        "ruby" => "rb",
+        _ => "sh",
+    }
+}
+
+/** Shells that take a multi-line script inline with `-c`, so it never has to be written to disk. */
+fn runs_scripts_inline(name: &str) -> bool {
+    matches!(name, "sh" | "bash" | "zsh" | "dash" | "ksh" | "mksh" | "ash" | "fish")
+}
 
+/**
+ Write a multi-line script to a file in a new directory in the temp dir. The directory
+ is only accessible by the current user and neither is created if it already exists,
+ as the script may contain secrets.
+*/
+fn write_temp_script(script: &str, extension: &str) -> Result<TempScript, String> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let dir = std::env::temp_dir().join(format!("scli-{}-{}", std::process::id(), nanos));
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+        .create(&dir)
+        .map_err(|e| format!("Failed to create script directory {:?}: {}", dir, e))?;
+
+    let temp = TempScript { path: dir.join(format!("script.{}", extension)), dir };
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&temp.path)
+        .and_then(|mut file| file.write_all(script.as_bytes()))
+        .map_err(|e| format!("Failed to write script {:?}: {}", temp.path, e))?;
+    Ok(temp)
+}
+
+/**
+ Build the command running `cmd` with the configured shell and options applied.
+ Commands are passed inline (`sh -c`, `node -e`, ...), except multi-line scripts for
+ interpreters other than the `sh` family, which are written to a private temp file
+ that the interpreter runs. The returned guard removes that file and must be kept
+ until the process has finished.
+*/
+fn shell_command(cmd: &str, opts: &ExecOptions) -> Result<(Command, Option<TempScript>), String> {
+    #[cfg(target_os = "windows")]
+    const DEFAULT_SHELL: &str = "cmd";
     #[cfg(not(target_os = "windows"))]
-    let output = Command::new("sh").arg("-c").arg(cmd).status();
+    const DEFAULT_SHELL: &str = "sh";
+
+    let shell = opts.shell.as_deref().map(str::trim).filter(|s| !s.is_empty()).unwrap_or(DEFAULT_SHELL);
+    let mut parts = shell.split_whitespace();
+    let program = parts.next().unwrap_or(DEFAULT_SHELL);
+    let name = interpreter_name(program);
+
+    let mut command = Command::new(program);
+    command.args(parts);
+
+    let script = cmd.trim();
+    let mut temp = None;
+    if !runs_scripts_inline(&name) && script.lines().filter(|l| !l.trim().is_empty()).count() > 1 {
+        let file = write_temp_script(&format!("{}\n", script), script_extension(&name))?;
+        match name.as_str() {
+            "pwsh" | "powershell" => command.arg("-File"),
+            "cmd" => command.arg("/C"),
+            _ => &mut command,
+        };
+        command.arg(&file.path);
+        temp = Some(file);
+    } else {
+        command.arg(inline_flag(&name)).arg(cmd);
+    }
+
+    command.envs(&opts.env);
+    if let Some(cwd) = &opts.cwd {
+        command.current_dir(cwd);
+    }
+    Ok((command, temp))
+}
+
+/** Execute a shell command using the configured shell and wait for it, up to the timeout if one is set. */
+pub fn execute_shell_command(cmd: &str, opts: &ExecOptions) -> Result<ExecStatus, String> {
+    run_shell_command(cmd, opts, false).map(|(status, _)| status)
+}
+
+/** Like `execute_shell_command`, but the command's stdout is captured and returned instead of shown. */
+pub fn execute_capturing_stdout(cmd: &str, opts: &ExecOptions) -> Result<(ExecStatus, String), String> {
+    run_shell_command(cmd, opts, true)
+}
+
+fn run_shell_command(cmd: &str, opts: &ExecOptions, capture: bool) -> Result<(ExecStatus, String), String> {
+    let (mut command, _script) = shell_command(cmd, opts)?;
+    if capture || opts.label.is_some() {
+        command.stdout(Stdio::piped());
+    }
+    if opts.label.is_some() {
+        command.stdin(Stdio::null()).stderr(Stdio::piped());
+    }
+
+    // Own process group, so that everything the command started can be killed together
+    #[cfg(unix)]
+    if opts.timeout.is_some() {
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    }
+
+    #[cfg(unix)]
+    let _interrupts = signals::DeferInterrupts::install();
+
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to execute command: {}", e))?;
+
+    // read on separate threads, so a full pipe cannot block the command
+    let reader = child.stdout.take().map(|mut stdout| match opts.label.clone() {
+        Some(label) if !capture => thread::spawn(move || {
+            forward_lines(stdout, &label, false);
+            Vec::new()
+        }),
+        _ => thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            buf
+        }),
+    });
+    let forwarder = child
+        .stderr
+        .take()
+        .zip(opts.label.clone())
+        .map(|(stderr, label)| thread::spawn(move || forward_lines(stderr, &label, true)));
+
+    let status = match opts.timeout {
+        // a labelled command runs alongside others, so it does not get the terminal
+        Some(timeout) => wait_with_timeout(&mut child, timeout, opts.label.is_none())?,
+        None => child
+            .wait()
+            .map(|status| ExecStatus::Exited(exit_code(status)))
+            .map_err(|e| format!("Failed to wait for command: {}", e))?,
+    };
+
+    if let Some(forwarder) = forwarder {
+        let _ = forwarder.join();
+    }
+    let stdout = reader
+        .and_then(|r| r.join().ok())
+        .map(|buf| String::from_utf8_lossy(&buf).into_owned())
+        .unwrap_or_default();
+    Ok((status, stdout))
+}
+
+/** Wait for a command started in its own process group, killing it when the timeout expires.
+    Ctrl-C received by scli meanwhile is passed on to the group (see `signals::ForwardInterrupts`). */
+fn wait_with_timeout(child: &mut Child, timeout: Duration, foreground: bool) -> Result<ExecStatus, String> {
+    #[cfg(unix)]
+    let _forward = signals::ForwardInterrupts::register(child.id() as i32);
+    #[cfg(unix)]
+    let _foreground = foreground.then(|| terminal::Foreground::hand_over(child.id() as i32));
+    #[cfg(not(unix))]
+    let _ = foreground;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Ok(ExecStatus::Exited(exit_code(status))),
+            Ok(None) if Instant::now() >= deadline => {
+                kill(child);
+                return Ok(ExecStatus::TimedOut);
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(format!("Failed to wait for command: {}", e)),
+        }
+    }
+}
+
+/** Stop a timed-out command: SIGTERM to its process group, then SIGKILL after a grace period. */
+#[cfg(unix)]
+fn kill(child: &mut Child) {
+    let pgid = child.id() as i32;
+    unsafe { libc::kill(-pgid, libc::SIGTERM) };
+
+    let deadline = Instant::now() + KILL_GRACE;
+    while Instant::now() < deadline && matches!(child.try_wait(), Ok(None)) {
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    // also catches processes that ignored SIGTERM or outlived the shell
+    unsafe { libc::kill(-pgid, libc::SIGKILL) };
+    let _ = child.wait();
+}
+
+/** Stop a timed-out command. */
+#[cfg(not(unix))]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::{Mutex, MutexGuard};
+
+    /** Number of live guards, and the handler to restore when the last one is dropped */
+    static DEFERRED: Mutex<(usize, libc::sighandler_t)> = Mutex::new((0, libc::SIG_DFL));
+
+    /**
+     While alive, Ctrl-C (SIGINT) stops only the running command and not scli, which
+     waits for it and can still run post-commands and hooks. The handler only forwards the
+     signal (see `ForwardInterrupts`) rather than being SIG_IGN, so the command gets the
+     default handling back when it starts.
+     Guards of commands running in parallel share the handler until the last one is dropped.
+    */
+    pub struct DeferInterrupts;
+
+    /** Process groups of running commands that do not get Ctrl-C from the terminal themselves */
+    static FORWARD_TO: [AtomicI32; 64] = [const { AtomicI32::new(0) }; 64];
+
+    /** Passes SIGINT on to the registered process groups (only async-signal-safe calls). */
+    extern "C" fn forward(_: libc::c_int) {
+        for slot in &FORWARD_TO {
+            let pgid = slot.load(Ordering::SeqCst);
+            if pgid > 0 {
+                unsafe { libc::kill(-pgid, libc::SIGINT) };
+            }
+        }
+    }
+
+    fn deferred() -> MutexGuard<'static, (usize, libc::sighandler_t)> {
+        DEFERRED.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    impl DeferInterrupts {
+        pub fn install() -> Self {
+            let mut deferred = deferred();
+            if deferred.0 == 0 {
+                let handler = forward as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                deferred.1 = unsafe { libc::signal(libc::SIGINT, handler) };
+            }
+            deferred.0 += 1;
+            DeferInterrupts
+        }
+    }
+
+    impl Drop for DeferInterrupts {
+        fn drop(&mut self) {
+            let mut deferred = deferred();
+            deferred.0 -= 1;
+            if deferred.0 == 0 {
+                unsafe { libc::signal(libc::SIGINT, deferred.1) };
+            }
+        }
+    }
+
+    /**
+     While alive, Ctrl-C received by scli is forwarded to a command running in its own
+     process group (one with a timeout), which the terminal does not signal when the
+     command runs in parallel with others or scli kept the terminal.
+    */
+    pub struct ForwardInterrupts {
+        slot: Option<&'static AtomicI32>,
+    }
+
+    impl ForwardInterrupts {
+        pub fn register(pgid: libc::pid_t) -> Self {
+            let slot = FORWARD_TO
+                .iter()
+                .find(|slot| slot.compare_exchange(0, pgid, Ordering::SeqCst, Ordering::SeqCst).is_ok());
+            if slot.is_none() {
+                log::debug!("Too many commands running; Ctrl-C is not forwarded to process group {}", pgid);
+            }
+            ForwardInterrupts { slot }
+        }
+    }
+
+    impl Drop for ForwardInterrupts {
+        fn drop(&mut self) {
+            if let Some(slot) = self.slot {
+                slot.store(0, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+mod terminal {
+    /**
+     Gives the terminal to a command running in its own process group, so it can read
+     input and receives Ctrl-C, and takes it back when dropped. Does nothing when stdin
+     is not a terminal or scli itself is not in the foreground.
+    */
+    pub struct Foreground {
+        restore: Option<libc::pid_t>,
+    }
+
+    impl Foreground {
+        pub fn hand_over(pgid: libc::pid_t) -> Self {
+            unsafe {
+                let ours = libc::getpgrp();
+                if libc::isatty(libc::STDIN_FILENO) == 0 || libc::tcgetpgrp(libc::STDIN_FILENO) != ours {
+                    return Foreground { restore: None };
+                }
+                set_foreground(pgid);
+                // the command may have been stopped reading input before it got the terminal
+                libc::kill(-pgid, libc::SIGCONT);
+                Foreground { restore: Some(ours) }
+            }
+        }
+    }
+
+    impl Drop for Foreground {
+        fn drop(&mut self) {
+            if let Some(pgid) = self.restore {
+                unsafe { set_foreground(pgid) };
+            }
+        }
+    }
+
+    /** Make `pgid` the terminal's foreground process group (SIGTTOU is ignored meanwhile,
+        as a background process changing it would otherwise be stopped). */
+    unsafe fn set_foreground(pgid: libc::pid_t) {
+        unsafe {
+            let previous = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+            libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+            libc::signal(libc::SIGTTOU, previous);
+        }
+    }
+}
+
+/** Exit code of a finished process. A process killed by a signal maps to 128 + signal, as in the shell. */
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/** Execute a shell command with the given env, shell and cwd and capture its stdout. Stderr is passed through to the terminal.
+    Returns an error if the command cannot be run or exits with a non-zero status. */
+pub fn capture_shell_command(cmd: &str, opts: &ExecOptions) -> Result<String, String> {
+    let (mut command, _script) = shell_command(cmd, opts)?;
+    let output = command.stderr(Stdio::inherit()).output();
 
-    match output {
-        Ok(status) => Ok(status.code().unwrap_or_default()),
-        Err(e) => Err(format!("Failed to execute command: {}", e)),
+    let output = output.map_err(|e| format!("Failed to execute command: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Command `{}` failed with exit code {}",
+            cmd,
+            exit_code(output.status)
+        ));
     }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
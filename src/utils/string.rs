@@ -1,4 +1,5 @@
-/** String utilities */
+/*! String utilities */
+use std::time::Duration;
 
 /** Trim and collapse whitespace to single spaces. */
 #[allow(dead_code)]
@@ -15,3 +16,155 @@ pub fn normalize_whitespace(s: &str) -> String {
     }
     out.trim().to_string()
 }
+
+/** Replace every occurrence of the given secret values with `****`. Longer secrets are replaced first. */
+pub fn redact(s: &str, secrets: &[String]) -> String {
+    let mut secrets: Vec<&String> = secrets.iter().filter(|v| !v.is_empty()).collect();
+    secrets.sort_by_key(|v| std::cmp::Reverse(v.len()));
+
+    let mut out = s.to_string();
+    for secret in secrets {
+        out = out.replace(secret.as_str(), "****");
+    }
+    out
+}
+
+/** Expand `${VAR}` and `${VAR:-fallback}` references to environment variables.
+    Unset variables without a fallback expand to an empty string; `$${` escapes a literal `${`. */
+pub fn expand_env_vars(s: &str) -> String {
+    let re = regex::Regex::new(r"\$(\$)?\{([A-Za-z_][A-Za-z0-9_]*)(?::-([^}]*))?\}").unwrap();
+    re.replace_all(s, |caps: &regex::Captures| {
+        if caps.get(1).is_some() {
+            return caps[0][1..].to_string();
+        }
+        let name = &caps[2];
+        match std::env::var(name) {
+            Ok(value) if !value.is_empty() || caps.get(3).is_none() => value,
+            _ => match caps.get(3) {
+                Some(fallback) => fallback.as_str().to_string(),
+                None => {
+                    log::warn!("Environment variable '{}' is not set", name);
+                    String::new()
+                }
+            },
+        }
+    })
+    .into_owned()
+}
+
+/** Expand a leading `~` to the user's home directory. */
+pub fn expand_home(raw: &str) -> String {
+    if (raw == "~" || raw.starts_with("~/")) && let Some(home) = dirs::home_dir() {
+        return format!("{}{}", home.display(), &raw[1..]);
+    }
+    raw.to_string()
+}
+
+/** Parse a duration such as `30s`, `5m`, `1h30m`, `500ms` or a plain number of seconds. */
+pub fn parse_duration(raw: &str) -> Result<Duration, String> {
+    let raw = raw.trim();
+    if let Ok(seconds) = raw.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|e| format!("Invalid duration '{}': {}", raw, e));
+    }
+
+    let full_re = regex::Regex::new(r"^(?:\d+(?:\.\d+)?(?:ms|s|m|h))+$").unwrap();
+    if !full_re.is_match(raw) {
+        return Err(format!("Invalid duration '{}': expected e.g. 30s, 5m, 1h or 500ms", raw));
+    }
+
+    let part_re = regex::Regex::new(r"(\d+(?:\.\d+)?)(ms|s|m|h)").unwrap();
+    let seconds: f64 = part_re
+        .captures_iter(raw)
+        .map(|caps| {
+            let n: f64 = caps[1].parse().unwrap_or_default();
+            match &caps[2] {
+                "ms" => n / 1000.0,
+                "m" => n * 60.0,
+                "h" => n * 3600.0,
+                _ => n,
+            }
+        })
+        .sum();
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("Invalid duration '{}': {}", raw, e))
+}
+
+/** Format a duration in the largest whole unit, e.g. `90s`, `5m` or `250ms`. */
+pub fn format_duration(duration: Duration) -> String {
+    let ms = duration.as_millis();
+    match ms {
+        0 => "0s".to_string(),
+        ms if ms % 3_600_000 == 0 => format!("{}h", ms / 3_600_000),
+        ms if ms % 60_000 == 0 => format!("{}m", ms / 60_000),
+        ms if ms % 1000 == 0 => format!("{}s", ms / 1000),
+        ms => format!("{}ms", ms),
+    }
+}
+
+/** Elapsed time with one decimal, e.g. `12.3s`. */
+pub fn format_elapsed(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // each test uses its own variables, as tests run in parallel
+    fn set_env(name: &str, value: &str) {
+        unsafe { std::env::set_var(name, value) };
+    }
+
+    #[test]
+    fn expands_env_vars() {
+        set_env("SCLI_TEST_EXPAND", "value");
+        assert_eq!(expand_env_vars("a ${SCLI_TEST_EXPAND} b"), "a value b");
+        assert_eq!(expand_env_vars("${SCLI_TEST_EXPAND:-fallback}"), "value");
+        assert_eq!(expand_env_vars("${SCLI_TEST_UNSET_NO_FALLBACK}"), "");
+    }
+
+    #[test]
+    fn uses_the_fallback_for_unset_and_empty_env_vars() {
+        set_env("SCLI_TEST_EMPTY", "");
+        assert_eq!(expand_env_vars("${SCLI_TEST_UNSET:-fallback}"), "fallback");
+        assert_eq!(expand_env_vars("${SCLI_TEST_EMPTY:-fallback}"), "fallback");
+        assert_eq!(expand_env_vars("${SCLI_TEST_UNSET:-}"), "");
+    }
+
+    #[test]
+    fn keeps_escaped_env_var_references() {
+        set_env("SCLI_TEST_ESCAPED", "value");
+        assert_eq!(expand_env_vars("$${SCLI_TEST_ESCAPED}"), "${SCLI_TEST_ESCAPED}");
+        assert_eq!(expand_env_vars("$${SCLI_TEST_ESCAPED:-x} ${SCLI_TEST_ESCAPED}"), "${SCLI_TEST_ESCAPED:-x} value");
+    }
+
+    #[test]
+    fn parses_durations_with_units() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration(" 1m30s "), Ok(Duration::from_secs(90)));
+    }
+
+    #[test]
+    fn parses_plain_numbers_as_seconds() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for raw in ["1m30", "-5", "-1m", "", "5x", "m5", "1h 30m"] {
+            assert!(parse_duration(raw).is_err(), "'{}' should be rejected", raw);
+        }
+    }
+
+    #[test]
+    fn formats_durations_in_the_largest_whole_unit() {
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+        assert_eq!(format_duration(Duration::from_millis(250)), "250ms");
+        assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+        assert_eq!(format_duration(Duration::from_secs(300)), "5m");
+        assert_eq!(format_duration(Duration::from_secs(7200)), "2h");
+    }
+}